@@ -0,0 +1,136 @@
+use std::collections::BTreeSet;
+
+use nix_rs::{
+    config::machines::{Machine, MachineError},
+    flake::system::System,
+    info,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::traits::*;
+
+/// Check that the remote builders (the `builders` setting) are well-formed,
+/// and that they can build for the required systems.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Builders {
+    /// Systems that must be buildable, either locally or on a builder
+    ///
+    /// When checking a flake, defaults to the `systems` of its `om.ci`
+    /// configuration.
+    pub required_systems: Vec<System>,
+}
+
+impl Checkable for Builders {
+    fn check(
+        &self,
+        nix_info: &info::NixInfo,
+        _: Option<&nix_rs::flake::url::FlakeUrl>,
+    ) -> Vec<Check> {
+        let builders = nix_info.nix_config.builders();
+        if builders.is_empty() && self.required_systems.is_empty() {
+            return vec![];
+        }
+        let (machines, errors): (Vec<_>, Vec<_>) = builders.into_iter().partition(Result::is_ok);
+        let machines: Vec<Machine> = machines.into_iter().flatten().collect();
+        let errors: Vec<MachineError> = errors.into_iter().filter_map(Result::err).collect();
+
+        // Keys we cannot stat (eg: `/root/.ssh/id_ed25519`, when not running
+        // as root) may well exist, so they are only reported as unverified.
+        let mut missing_keys: Vec<String> = vec![];
+        let mut unverified_keys: Vec<String> = vec![];
+        for key in machines.iter().filter_map(|m| m.ssh_key.as_ref()) {
+            match key.try_exists() {
+                Ok(true) => {}
+                Ok(false) => missing_keys.push(key.display().to_string()),
+                Err(_) => unverified_keys.push(key.display().to_string()),
+            }
+        }
+        let missing_systems: Vec<&System> = {
+            let buildable = buildable_systems(nix_info, &machines);
+            self.required_systems
+                .iter()
+                .filter(|s| !buildable.contains(*s))
+                .collect()
+        };
+
+        let config_label = nix_info.nix_env.os.nix_setting_label(
+            nix_info
+                .nix_config
+                .setting("builders")
+                .and_then(|v| v.source.as_ref()),
+        );
+        let result = if !errors.is_empty() {
+            CheckResult::Red {
+                msg: format!(
+                    "Invalid builders: {}",
+                    errors
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ),
+                suggestion: format!(
+                    "Fix the `builders` setting in {} (see https://nix.dev/manual/nix/stable/advanced-topics/distributed-builds)",
+                    config_label
+                ),
+            }
+        } else if !missing_keys.is_empty() {
+            CheckResult::Red {
+                msg: format!("SSH keys of builders not found: {}", missing_keys.join(" ")),
+                suggestion: "Create the SSH keys, and make sure they are readable by the Nix daemon (which runs as root)".to_string(),
+            }
+        } else if !missing_systems.is_empty() {
+            let missing_systems = missing_systems
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            CheckResult::Red {
+                msg: format!("No builder for systems: {}", missing_systems),
+                suggestion: format!(
+                    "Add a builder for {} to the `builders` setting in {} (see https://nix.dev/manual/nix/stable/advanced-topics/distributed-builds)",
+                    missing_systems, config_label
+                ),
+            }
+        } else {
+            CheckResult::Green
+        };
+        let mut info = format!(
+            "builders = {}",
+            machines
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+        if !unverified_keys.is_empty() {
+            info.push_str(&format!(
+                " (cannot access SSH keys: {})",
+                unverified_keys.join(" ")
+            ));
+        }
+        let check = Check {
+            title: "Remote Builders".to_string(),
+            info,
+            result,
+            required: !self.required_systems.is_empty(),
+        };
+        vec![check]
+    }
+}
+
+/// Systems that can be built locally or on one of the given machines
+fn buildable_systems(nix_info: &info::NixInfo, machines: &[Machine]) -> BTreeSet<System> {
+    let config = &nix_info.nix_config;
+    std::iter::once(config.system.value.clone())
+        .chain(
+            config
+                .extra_platforms
+                .value
+                .iter()
+                .map(|s| System::from(s.as_str())),
+        )
+        .chain(machines.iter().flat_map(|m| m.systems.iter().cloned()))
+        .collect()
+}
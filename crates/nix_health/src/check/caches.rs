@@ -46,7 +46,10 @@ impl Checkable for Caches {
                 ),
                 suggestion: format!(
                     "Caches can be added in your {} (see https://nixos.wiki/wiki/Binary_Cache#Using_a_binary_cache). Cachix caches can also be added using `nix run nixpkgs#cachix use <name>`.",
-                    nix_info.nix_env.os.nix_config_label()
+                    nix_info
+                        .nix_env
+                        .os
+                        .nix_setting_label(nix_info.nix_config.substituters.source.as_ref())
                 )
             }
         };
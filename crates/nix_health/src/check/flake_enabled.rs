@@ -25,7 +25,12 @@ impl Checkable for FlakeEnabled {
             } else {
                 CheckResult::Red {
                     msg: "Nix flakes are not enabled".into(),
-                    suggestion: "See https://nixos.wiki/wiki/Flakes#Enable_flakes".into(),
+                    suggestion: format!(
+                        "Set `experimental-features = nix-command flakes` in {} (see https://nixos.wiki/wiki/Flakes#Enable_flakes)",
+                        nix_info.nix_env.os.nix_setting_label(
+                            nix_info.nix_config.experimental_features.source.as_ref()
+                        )
+                    ),
                 }
             },
             required: true,
@@ -0,0 +1,65 @@
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::{Check, CheckResult, Checkable};
+
+/// Check that the volume of the Nix store has enough free space
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FreeSpace {
+    enable: bool,
+    required: bool,
+    /// Minimum free disk space on the volume of the Nix store
+    min_free_space: ByteSize,
+}
+
+impl Default for FreeSpace {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            required: false,
+            min_free_space: ByteSize::gb(10),
+        }
+    }
+}
+
+impl Checkable for FreeSpace {
+    fn check(
+        &self,
+        nix_info: &nix_rs::info::NixInfo,
+        _: Option<&nix_rs::flake::url::FlakeUrl>,
+    ) -> Vec<Check> {
+        if !self.enable {
+            return vec![];
+        }
+        let nix_env = &nix_info.nix_env;
+        // Only computed here, as walking the store can be slow
+        let store_size = nix_env
+            .install
+            .store_size()
+            .map_or("unknown".to_string(), |size| size.to_string());
+        let check = Check {
+            title: "Free Disk Space".to_string(),
+            info: format!(
+                "min free space = {}; free = {} (of {}); store size = {}",
+                self.min_free_space,
+                nix_env.available_disk_space,
+                nix_env.total_disk_space,
+                store_size
+            ),
+            result: if nix_env.available_disk_space < self.min_free_space {
+                CheckResult::Red {
+                    msg: format!(
+                        "Free space on the Nix store volume is less than {}",
+                        self.min_free_space
+                    ),
+                    suggestion: "Free up space by running `nix-collect-garbage --delete-older-than 30d` (as root, to also delete old system generations)".to_string(),
+                }
+            } else {
+                CheckResult::Green
+            },
+            required: self.required,
+        };
+        vec![check]
+    }
+}
@@ -25,7 +25,10 @@ impl Checkable for MaxJobs {
                     msg: "You are using only 1 CPU core for nix builds".into(),
                     suggestion: format!(
                         "Set `max-jobs = auto` in {}",
-                        nix_info.nix_env.os.nix_config_label()
+                        nix_info
+                            .nix_env
+                            .os
+                            .nix_setting_label(nix_info.nix_config.max_jobs.source.as_ref())
                     ),
                 }
             },
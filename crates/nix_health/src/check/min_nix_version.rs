@@ -1,4 +1,6 @@
-use nix_rs::version::NixVersion;
+use std::collections::BTreeMap;
+
+use nix_rs::version::{NixImpl, NixVersion};
 
 use nix_rs::info;
 use serde::{Deserialize, Serialize};
@@ -10,16 +12,32 @@ use crate::traits::*;
 #[serde(default, rename_all = "kebab-case")]
 pub struct MinNixVersion {
     pub min_required: NixVersion,
+    /// Minimum versions for specific implementations of Nix, overriding
+    /// `min_required`
+    ///
+    /// Needed for implementations with their own version numbers (eg: Lix).
+    pub min_required_for: BTreeMap<NixImpl, NixVersion>,
 }
 
 impl Default for MinNixVersion {
     fn default() -> Self {
         MinNixVersion {
-            min_required: NixVersion {
-                major: 2,
-                minor: 16,
-                patch: 0,
-            },
+            min_required: NixVersion::new(2, 16, 0),
+            min_required_for: BTreeMap::from([(NixImpl::Lix, NixVersion::new(2, 90, 0))]),
+        }
+    }
+}
+
+impl MinNixVersion {
+    /// The minimum version required for the given implementation
+    pub fn min_required(&self, implementation: NixImpl) -> NixVersion {
+        let min = self
+            .min_required_for
+            .get(&implementation)
+            .unwrap_or(&self.min_required);
+        NixVersion {
+            implementation,
+            ..min.clone()
         }
     }
 }
@@ -31,15 +49,20 @@ impl Checkable for MinNixVersion {
         _: Option<&nix_rs::flake::url::FlakeUrl>,
     ) -> Vec<Check> {
         let val = &nix_info.nix_version;
+        let min_required = self.min_required(val.implementation);
         let check = Check {
             title: "Minimum Nix Version".to_string(),
             info: format!("nix version = {}", val),
-            result: if val >= &self.min_required {
+            result: if val >= &min_required {
                 CheckResult::Green
             } else {
                 CheckResult::Red {
-                    msg: format!("Your Nix version ({}) is too old; we require at least {}", val, self.min_required),
-                    suggestion: "See https://nixos.org/manual/nix/stable/command-ref/new-cli/nix3-upgrade-nix.html".into(),
+                    msg: format!("Your Nix version ({}) is too old; we require at least {}", val, min_required),
+                    suggestion: match val.implementation {
+                        NixImpl::CppNix => "See https://nixos.org/manual/nix/stable/command-ref/new-cli/nix3-upgrade-nix.html".into(),
+                        NixImpl::Lix => "See https://lix.systems/install/".into(),
+                        NixImpl::Determinate => "Run `sudo determinate-nixd upgrade`".into(),
+                    },
                 }
             },
             required: true,
@@ -1,7 +1,9 @@
 //! Individual Nix checks
+pub mod builders;
 pub mod caches;
 pub mod direnv;
 pub mod flake_enabled;
+pub mod free_space;
 pub mod max_jobs;
 pub mod min_nix_version;
 pub mod rosetta;
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use nix_rs::config::TrustedUserValue;
+use nix_rs::config::{file::ConfigSource, TrustedUserValue};
 use serde::{Deserialize, Serialize};
 
 use crate::traits::*;
@@ -16,7 +16,8 @@ impl Checkable for TrustedUsers {
         nix_info: &nix_rs::info::NixInfo,
         _: Option<&nix_rs::flake::url::FlakeUrl>,
     ) -> Vec<Check> {
-        let result = if is_current_user_trusted(nix_info) {
+        // In single-user installs, the user owns the store and is thus trusted
+        let result = if !nix_info.nix_env.install.multi_user || is_current_user_trusted(nix_info) {
             CheckResult::Green
         } else {
             let current_user = &nix_info.nix_env.current_user;
@@ -26,10 +27,22 @@ impl Checkable for TrustedUsers {
                     r#"Add `nix.trustedUsers = [ "root" "{}" ];` to your {}"#,
                     current_user, conf_label,
                 ),
-                None => format!(
-                    r#"Set `trusted-users = root {}` in /etc/nix/nix.conf and then restart the Nix daemon using `sudo pkill nix-daemon`"#,
-                    current_user
-                ),
+                None => {
+                    // Only the system configuration is read by the Nix daemon
+                    let conf_file = match &nix_info.nix_config.trusted_users.source {
+                        Some(ConfigSource::SystemFile(path)) => path.display().to_string(),
+                        _ => "/etc/nix/nix.conf".to_string(),
+                    };
+                    let restart = nix_info
+                        .nix_env
+                        .install
+                        .daemon_restart_command(&nix_info.nix_env.os)
+                        .unwrap_or("sudo pkill nix-daemon");
+                    format!(
+                        r#"Set `trusted-users = root {}` in {} and then restart the Nix daemon using `{}`"#,
+                        current_user, conf_file, restart
+                    )
+                }
             };
             CheckResult::Red { msg, suggestion }
         };
@@ -4,6 +4,8 @@ pub mod check;
 pub mod report;
 pub mod traits;
 
+use std::collections::{BTreeMap, BTreeSet};
+
 use anyhow::Context;
 use colored::Colorize;
 
@@ -16,8 +18,9 @@ use tracing::instrument;
 use traits::Check;
 
 use self::check::{
-    caches::Caches, flake_enabled::FlakeEnabled, max_jobs::MaxJobs, min_nix_version::MinNixVersion,
-    rosetta::Rosetta, trusted_users::TrustedUsers,
+    builders::Builders, caches::Caches, flake_enabled::FlakeEnabled, free_space::FreeSpace,
+    max_jobs::MaxJobs, min_nix_version::MinNixVersion, rosetta::Rosetta,
+    trusted_users::TrustedUsers,
 };
 
 /// Nix Health check information for user's install
@@ -31,9 +34,11 @@ pub struct NixHealth {
     pub flake_enabled: FlakeEnabled,
     pub nix_version: MinNixVersion,
     pub system: check::system::System,
+    pub free_space: FreeSpace,
     pub trusted_users: TrustedUsers,
     pub rosetta: Rosetta,
     pub direnv: Direnv,
+    pub builders: Builders,
 }
 
 impl<'a> IntoIterator for &'a NixHealth {
@@ -47,10 +52,12 @@ impl<'a> IntoIterator for &'a NixHealth {
             &self.nix_version,
             &self.flake_enabled,
             &self.system,
+            &self.free_space,
             &self.max_jobs,
             &self.caches,
             &self.trusted_users,
             &self.direnv,
+            &self.builders,
         ];
         items.into_iter()
     }
@@ -64,8 +71,11 @@ impl NixHealth {
     pub async fn from_flake(url: &FlakeUrl) -> Result<Self, QualifiedAttrError> {
         let cmd = NixCmd::get().await;
         let flake_attr = RootQualifiedAttr::new(&["om.health", "nix-health"]);
-        let (v, _, rest_attrs) = flake_attr.eval_flake(cmd, url).await?;
+        let (mut v, _, rest_attrs): (NixHealth, _, _) = flake_attr.eval_flake(cmd, url).await?;
         if rest_attrs.is_empty() {
+            if v.builders.required_systems.is_empty() {
+                v.builders.required_systems = ci_systems(cmd, url).await;
+            }
             Ok(v)
         } else {
             Err(QualifiedAttrError::UnexpectedNestedAttribute(
@@ -128,6 +138,30 @@ impl NixHealth {
     }
 }
 
+/// The systems whitelisted in the `om.ci` configuration of the given flake
+async fn ci_systems(cmd: &NixCmd, url: &FlakeUrl) -> Vec<nix_rs::flake::system::System> {
+    #[derive(Deserialize)]
+    struct SubFlake {
+        systems: Option<Vec<nix_rs::flake::system::System>>,
+    }
+    let flake_attr = RootQualifiedAttr::new(&["om.ci", "nixci"]);
+    match flake_attr
+        .eval_flake::<BTreeMap<String, SubFlake>>(cmd, url)
+        .await
+    {
+        Ok((subflakes, _, _)) => subflakes
+            .into_values()
+            .flat_map(|sub| sub.systems.unwrap_or_default())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        Err(err) => {
+            tracing::debug!("Unable to read om.ci systems: {}", err);
+            vec![]
+        }
+    }
+}
+
 /// Run health checks, optionally using the given flake's configuration
 pub async fn run_checks_with(flake_url: Option<FlakeUrl>) -> anyhow::Result<Vec<Check>> {
     let nix_info = NixInfo::get()
@@ -216,6 +250,21 @@ mod tests {
         assert_eq!(v.caches, Caches::default());
     }
 
+    #[test]
+    fn test_json_deserialize_nix_version_per_implementation() {
+        use nix_rs::version::NixImpl;
+        let json = r#"{ "nix-version": { "min-required-for": { "lix": "2.91.0" } } }"#;
+        let v: super::NixHealth = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            v.nix_version.min_required(NixImpl::Lix).to_string(),
+            "Lix 2.91.0"
+        );
+        assert_eq!(
+            v.nix_version.min_required(NixImpl::Determinate).to_string(),
+            "Determinate Nix 2.16.0"
+        );
+    }
+
     #[test]
     fn test_json_deserialize_caches() {
         let json = r#"{ "caches": { "required": ["https://foo.cachix.org"] } }"#;
@@ -226,4 +275,26 @@ mod tests {
         );
         assert_eq!(v.nix_version, MinNixVersion::default());
     }
+
+    #[test]
+    fn test_json_deserialize_free_space() {
+        let json = r#"{ "free-space": { "min-free-space": "50 GB", "required": true } }"#;
+        let v: super::NixHealth = serde_json::from_str(json).unwrap();
+        let expected = serde_json::json!({
+            "enable": true,
+            "required": true,
+            "min-free-space": "50.0 GB"
+        });
+        assert_eq!(serde_json::to_value(v.free_space).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_json_deserialize_builders() {
+        let json = r#"{ "builders": { "required-systems": ["aarch64-linux"] } }"#;
+        let v: super::NixHealth = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            v.builders.required_systems,
+            vec![nix_rs::flake::system::System::from("aarch64-linux")]
+        );
+    }
 }
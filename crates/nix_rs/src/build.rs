@@ -0,0 +1,299 @@
+//! Rust module for `nix build`
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    path::PathBuf,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    command::{NixCmd, NixCmdError},
+    log::{Activity, ActivityId, ActivityResult, LogEvent, Verbosity},
+    store::{DrvOut, StorePath},
+};
+
+/// The result of building an installable, as reported by `nix build --json`
+///
+/// Installables that are opaque store paths (eg: `/nix/store/<hash>-foo/bin/foo`)
+/// have no derivation; Nix reports only their [BuildResult::path].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildResult {
+    /// The derivation that was built, unless the installable is an opaque
+    /// store path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drv_path: Option<StorePath>,
+    /// The output paths, keyed by output name (eg: `out`, `dev`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outputs: BTreeMap<String, DrvOut>,
+    /// The store path, if the installable is an opaque store path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<DrvOut>,
+    /// When the build started, in seconds since the epoch
+    ///
+    /// Absent in older versions of Nix, and zero if nothing was built (eg:
+    /// the outputs were substituted).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<u64>,
+    /// When the build finished, in seconds since the epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_time: Option<u64>,
+}
+
+impl BuildResult {
+    /// Run `nix build --json` on the given installables, returning a result
+    /// per installable (in order)
+    ///
+    /// No `result` symlinks are created; use [BuildResult::outputs] instead.
+    #[instrument(name = "build")]
+    pub async fn from_nix(
+        nix_cmd: &NixCmd,
+        installables: &[&str],
+        extra_args: &[&str],
+    ) -> Result<Vec<BuildResult>, NixCmdError> {
+        let args = [&["build", "--no-link", "--json"], installables, extra_args].concat();
+        nix_cmd.run_with_args_expecting_json(&args).await
+    }
+
+    /// The output paths of this build (or the opaque store path)
+    pub fn out_paths(&self) -> impl Iterator<Item = &DrvOut> {
+        self.path.iter().chain(self.outputs.values())
+    }
+
+    /// How long the build took, if it was built (rather than substituted)
+    pub fn duration(&self) -> Option<Duration> {
+        match (self.start_time, self.stop_time) {
+            (Some(start), Some(stop)) if start > 0 && stop >= start => {
+                Some(Duration::from_secs(stop - start))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Number of lines kept from the end of each build log, as Nix does by
+/// default (the `log-lines` setting)
+pub const LOG_TAIL_LINES: usize = 25;
+
+/// What a `nix build` did, gathered from its structured log (see [crate::log])
+#[derive(Debug, Default)]
+pub struct BuildLog {
+    /// The derivation being built by each build activity
+    activities: HashMap<ActivityId, StorePath>,
+    /// Derivations whose build started
+    pub started: BTreeSet<StorePath>,
+    /// Derivations that failed to build (excluding those whose dependencies
+    /// failed)
+    pub failed: BTreeSet<StorePath>,
+    /// Derivations that were not built because some of their dependencies
+    /// failed
+    pub dependency_failed: BTreeSet<StorePath>,
+    /// The last [LOG_TAIL_LINES] lines of the log of each build
+    tails: HashMap<StorePath, VecDeque<String>>,
+}
+
+impl BuildLog {
+    /// Account for the given log event
+    pub fn observe(&mut self, event: &LogEvent) {
+        match event {
+            LogEvent::Start {
+                id,
+                activity: Activity::Build { drv_path, .. },
+                ..
+            } => {
+                let drv = StorePath::new(PathBuf::from(drv_path));
+                self.started.insert(drv.clone());
+                self.activities.insert(*id, drv);
+            }
+            LogEvent::Result {
+                id,
+                result: ActivityResult::BuildLogLine(line),
+            } => {
+                if let Some(drv) = self.activities.get(id) {
+                    let tail = self.tails.entry(drv.clone()).or_default();
+                    if tail.len() == LOG_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line.clone());
+                }
+            }
+            LogEvent::Msg {
+                level: Verbosity::Error,
+                msg,
+            } => {
+                if let Some(drv) = failed_drv_in_error(msg) {
+                    self.failed.insert(drv);
+                } else if let Some(drv) = dependency_failed_drv_in_error(msg) {
+                    self.dependency_failed.insert(drv);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The derivation being built by the given activity
+    pub fn activity_drv(&self, id: ActivityId) -> Option<&StorePath> {
+        self.activities.get(&id)
+    }
+
+    /// The end of the build log of the given derivation, if it was built
+    pub fn log_tail(&self, drv: &StorePath) -> Vec<String> {
+        self.tails
+            .get(drv)
+            .map(|tail| tail.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The derivation that failed to build, according to the given error message
+///
+/// Recognizes the messages of a failed builder (`builder for '<drv>' failed
+/// ...` or `Cannot build '<drv>'`) and of a hash mismatch, but not those of
+/// a derivation whose dependencies failed.
+fn failed_drv_in_error(msg: &str) -> Option<StorePath> {
+    let msg = strip_ansi(msg);
+    if msg.contains("dependenc") || !(msg.contains("failed") || msg.contains("mismatch")) {
+        return None;
+    }
+    drv_in(&msg)
+}
+
+/// The derivation that was not built because its dependencies failed,
+/// according to the given error message (`1 dependencies of derivation
+/// '<drv>' failed to build`, or `Cannot build '<drv>'. Reason: 1 dependency
+/// failed.`)
+fn dependency_failed_drv_in_error(msg: &str) -> Option<StorePath> {
+    let msg = strip_ansi(msg);
+    if !(msg.contains("dependenc") && msg.contains("failed")) {
+        return None;
+    }
+    drv_in(&msg)
+}
+
+/// The first derivation quoted in the given message
+fn drv_in(msg: &str) -> Option<StorePath> {
+    msg.split('\'')
+        .find(|s| s.starts_with('/') && s.ends_with(".drv"))
+        .map(|drv| StorePath::new(PathBuf::from(drv)))
+}
+
+/// Remove the ANSI escape sequences (colors) from the given string
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip until the final byte of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::command::runner::{Recording, ReplayRunner};
+
+    #[tokio::test]
+    async fn test_replay_build() {
+        let stdout = r#"[
+            {"drvPath":"/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv","outputs":{"out":"/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1"},"startTime":1700000000,"stopTime":1700000042},
+            {"drvPath":"/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-openssl-3.0.13.drv","outputs":{"bin":"/nix/store/3ph5bq8jbyd1pn0i7aya0yyrzqfkh4rr-openssl-3.0.13-bin","out":"/nix/store/7kl3ghbqh8fb6b2wgx2nrbh7bqxqyl14-openssl-3.0.13"}},
+            {"path":"/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1"}
+        ]"#;
+        let installables = [
+            "nixpkgs#hello",
+            "nixpkgs#openssl",
+            "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1/bin/hello",
+        ];
+        let mut cmd = NixCmd::default();
+        cmd.with_runner(ReplayRunner::new(vec![Recording {
+            args: [&["build", "--no-link", "--json"], &installables[..]]
+                .concat()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+        }]));
+        let results = BuildResult::from_nix(&cmd, &installables, &[])
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].outputs["out"],
+            DrvOut(PathBuf::from(
+                "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1"
+            ))
+        );
+        assert_eq!(results[0].duration(), Some(Duration::from_secs(42)));
+        assert_eq!(results[1].out_paths().count(), 2);
+        assert_eq!(results[1].duration(), None);
+        // Opaque store paths are not built from a derivation
+        assert_eq!(results[2].drv_path, None);
+        assert_eq!(
+            results[2].out_paths().collect::<Vec<_>>(),
+            vec![&results[0].outputs["out"]]
+        );
+    }
+
+    #[test]
+    fn test_build_log() {
+        // Excerpt of `nix build --log-format internal-json --keep-going`, where
+        // `broken` fails and `app` depends on it
+        let log = r#"@nix {"action":"start","fields":["/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv","",1,1],"id":1,"level":3,"parent":0,"text":"building '/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv'","type":105}
+@nix {"action":"start","fields":["/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv","",1,1],"id":2,"level":3,"parent":0,"text":"building '/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv'","type":105}
+@nix {"action":"result","fields":["Running phase: buildPhase"],"id":1,"type":101}
+@nix {"action":"result","fields":["make: *** [Makefile:2: all] Error 1"],"id":1,"type":101}
+@nix {"action":"result","fields":["Hello, world!"],"id":2,"type":101}
+@nix {"action":"stop","id":1}
+@nix {"action":"msg","level":0,"msg":"\u001b[31;1merror:\u001b[0m builder for '\u001b[35;1m/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv\u001b[0m' failed with exit code 2"}
+@nix {"action":"msg","level":0,"msg":"\u001b[31;1merror:\u001b[0m 1 dependencies of derivation '\u001b[35;1m/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-app.drv\u001b[0m' failed to build"}"#;
+        let mut build_log = BuildLog::default();
+        for line in log.lines() {
+            if let crate::log::LogLine::Event(event) = crate::log::LogLine::parse(line).unwrap() {
+                build_log.observe(&event);
+            }
+        }
+        let drv = |s: &str| StorePath::new(PathBuf::from(s));
+        let broken = drv("/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv");
+        assert_eq!(build_log.started.len(), 2);
+        assert_eq!(build_log.failed, BTreeSet::from([broken.clone()]));
+        assert_eq!(
+            build_log.dependency_failed,
+            BTreeSet::from([drv("/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-app.drv")])
+        );
+        assert_eq!(
+            build_log.log_tail(&broken),
+            vec![
+                "Running phase: buildPhase",
+                "make: *** [Makefile:2: all] Error 1"
+            ]
+        );
+        // Nix >= 2.19 reports failures differently
+        assert_eq!(
+            failed_drv_in_error("error: Cannot build '/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv'.\n       Reason: builder failed with exit code 2."),
+            Some(broken)
+        );
+        assert_eq!(
+            failed_drv_in_error("error: Cannot build '/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-app.drv'.\n       Reason: 1 dependency failed."),
+            None
+        );
+        assert_eq!(
+            dependency_failed_drv_in_error("error: Cannot build '/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-app.drv'.\n       Reason: 1 dependency failed."),
+            Some(drv("/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-app.drv"))
+        );
+    }
+}
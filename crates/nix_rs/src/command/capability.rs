@@ -0,0 +1,153 @@
+//! Features of the `nix` CLI that only some versions have
+//!
+//! Use [super::NixCmd::supports] to pick an invocation that works with the
+//! `nix` at hand.
+use std::fmt::{self, Display};
+
+use crate::version::{NixImpl, NixVersion};
+
+/// A feature of the `nix` CLI that is not available in every version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// `nix config show`, which deprecates `nix show-config`
+    ConfigShow,
+    /// `nix build --print-out-paths`
+    PrintOutPaths,
+    /// `nix derivation show`, which deprecates `nix show-derivation`
+    DerivationShow,
+    /// `<drv>^<outputs>` installables, which deprecate `<drv>!<outputs>`
+    DrvOutputsCaret,
+}
+
+impl Capability {
+    /// The first Nix release with this capability
+    pub fn since(&self) -> NixVersion {
+        match self {
+            Capability::ConfigShow => NixVersion::new(2, 20, 0),
+            Capability::PrintOutPaths => NixVersion::new(2, 8, 0),
+            Capability::DerivationShow => NixVersion::new(2, 15, 0),
+            Capability::DrvOutputsCaret => NixVersion::new(2, 13, 0),
+        }
+    }
+
+    /// Whether the given version of Nix has this capability
+    pub fn supported_by(&self, version: &NixVersion) -> bool {
+        match version.implementation {
+            // Lix forked from Nix 2.18, and numbers its releases from 2.90
+            NixImpl::Lix => *self != Capability::ConfigShow,
+            // Determinate Nix reports the version of Nix it is based on
+            NixImpl::CppNix | NixImpl::Determinate => {
+                (version.major, version.minor, version.patch) >= {
+                    let since = self.since();
+                    (since.major, since.minor, since.patch)
+                }
+            }
+        }
+    }
+
+    /// The `nix` subcommand whose `--help` output documents this capability
+    pub fn help_args(&self) -> &'static [&'static str] {
+        match self {
+            Capability::ConfigShow => &["config", "show"],
+            Capability::PrintOutPaths => &["build"],
+            Capability::DerivationShow => &["derivation", "show"],
+            Capability::DrvOutputsCaret => &["build"],
+        }
+    }
+
+    /// Whether the `--help` output of [Capability::help_args] mentions this
+    /// capability
+    pub fn supported_in_help(&self, help: &str) -> bool {
+        match self {
+            Capability::ConfigShow => help.contains("nix config show"),
+            Capability::PrintOutPaths => help.contains("--print-out-paths"),
+            Capability::DerivationShow => help.contains("nix derivation show"),
+            // The examples select outputs, like `nixpkgs#glibc^dev`
+            Capability::DrvOutputsCaret => help.contains('^'),
+        }
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::ConfigShow => write!(f, "nix config show"),
+            Capability::PrintOutPaths => write!(f, "nix build --print-out-paths"),
+            Capability::DerivationShow => write!(f, "nix derivation show"),
+            Capability::DrvOutputsCaret => write!(f, "nix build <drv>^<outputs>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::command::{
+        runner::{Recording, ReplayRunner},
+        NixCmd,
+    };
+
+    /// Excerpts of `nix <subcommand> --help`, from Nix 2.18 and 2.24
+    fn help_recordings(nix_2_24: bool) -> Vec<Recording> {
+        let recording = |args: &[&str], stdout: &str, exit_code: i32| Recording {
+            args: args.iter().map(|s| s.to_string()).collect(),
+            stdout: stdout.to_string(),
+            stderr: if exit_code == 0 {
+                String::new()
+            } else {
+                "error: 'config' is not a recognised command\n".to_string()
+            },
+            exit_code,
+        };
+        vec![
+            if nix_2_24 {
+                recording(
+                    &["config", "show", "--help"],
+                    "Warning: this program is experimental and its interface is subject to change.\n\nName\n\n    nix config show - show the Nix configuration or the value of a specific setting\n\nSynopsis\n\n    nix config show [option...] name\n",
+                    0,
+                )
+            } else {
+                recording(&["config", "show", "--help"], "", 1)
+            },
+            recording(
+                &["build", "--help"],
+                "Options\n\n  • --dry-run\n    Show what this command would do without doing it.\n  • --json\n    Produce output in JSON format, suitable for consumption by another program.\n  • --no-link\n    Do not create symlinks to the build results.\n  • --print-out-paths\n    Print the resulting output paths\n",
+                0,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_supported_by() {
+        let v = |s: &str| NixVersion::from_str(s).unwrap();
+        assert!(!Capability::ConfigShow.supported_by(&v("nix (Nix) 2.18.1")));
+        assert!(Capability::ConfigShow.supported_by(&v("nix (Nix) 2.24.0pre20240101_abcdef")));
+        assert!(!Capability::ConfigShow.supported_by(&v("nix (Lix, like Nix) 2.90.0")));
+        assert!(Capability::PrintOutPaths.supported_by(&v("nix (Lix, like Nix) 2.90.0")));
+        assert!(Capability::ConfigShow.supported_by(&v("nix (Determinate Nix 3.0.0) 2.26.3")));
+        assert!(!Capability::PrintOutPaths.supported_by(&v("nix (Nix) 2.3")));
+        assert!(!Capability::DrvOutputsCaret.supported_by(&v("nix (Nix) 2.12.1")));
+        assert!(Capability::DrvOutputsCaret.supported_by(&v("nix (Lix, like Nix) 2.90.0")));
+    }
+
+    #[tokio::test]
+    async fn test_supports_from_help() {
+        for nix_2_24 in [false, true] {
+            let mut cmd = NixCmd::default();
+            cmd.with_runner(ReplayRunner::new(help_recordings(nix_2_24)));
+            assert_eq!(cmd.supports(Capability::ConfigShow).await, nix_2_24);
+            assert!(cmd.supports(Capability::PrintOutPaths).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supports_from_version() {
+        // The version, when known, takes precedence over probing
+        let mut cmd = NixCmd::default();
+        cmd.with_runner(ReplayRunner::new(help_recordings(true)));
+        cmd.with_version(NixVersion::new(2, 18, 1));
+        assert!(!cmd.supports(Capability::ConfigShow).await);
+    }
+}
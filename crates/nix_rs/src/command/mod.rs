@@ -7,20 +7,38 @@
 //! let cmd = NixCmd::default();
 //! cmd.run_with_args_returning_stdout(&["--version"]);
 //! ```
+pub mod capability;
+pub mod runner;
 
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    process::Stdio,
+};
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use tokio::{process::Command, sync::OnceCell};
+use tokio::{
+    process::{Child, Command},
+    sync::OnceCell,
+};
+use tokio_stream::Stream;
 
 use tracing::instrument;
 
 #[cfg(feature = "clap")]
 use clap;
 
-use crate::config::NixConfig;
+use crate::{
+    config::NixConfig,
+    log::{LogError, LogLine},
+    version::NixVersion,
+};
+
+use self::{
+    capability::Capability,
+    runner::{CommandRunner, Runner},
+};
 
 /// The `nix` command's global options.
 ///
@@ -44,6 +62,16 @@ pub struct NixCmd {
     /// The command to run instead of `nix`.
     #[cfg_attr(feature = "clap", arg(long))]
     pub command: Option<String>,
+
+    /// The version of the `nix` command, if known (see [NixCmd::supports])
+    #[cfg_attr(feature = "clap", arg(skip))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<NixVersion>,
+
+    /// How to execute the command (see [runner])
+    #[cfg_attr(feature = "clap", arg(skip))]
+    #[serde(skip)]
+    pub runner: Runner,
 }
 
 impl Default for NixCmd {
@@ -56,6 +84,8 @@ impl Default for NixCmd {
             extra_access_tokens: vec![],
             refresh: false,
             command: None,
+            version: None,
+            runner: Runner::default(),
         }
     }
 }
@@ -78,6 +108,8 @@ pub fn trace_cmd(cmd: &tokio::process::Command) {
 
 impl NixCmd {
     /// Return a global `NixCmd` instance with flakes enabled.
+    ///
+    /// Its [NixCmd::version] is detected by running `nix --version`.
     pub async fn get() -> &'static NixCmd {
         NIXCMD
             .get_or_init(|| async {
@@ -86,6 +118,10 @@ impl NixCmd {
                 if !cfg.is_flakes_enabled() {
                     cmd.with_flakes()
                 }
+                match NixVersion::from_nix(&cmd).await {
+                    Ok(version) => cmd.with_version(version),
+                    Err(err) => tracing::debug!("Unable to detect nix version: {}", err),
+                }
                 cmd
             })
             .await
@@ -97,6 +133,38 @@ impl NixCmd {
             .append(vec!["nix-command".to_string(), "flakes".to_string()].as_mut());
     }
 
+    /// Set the version of the `nix` command, as used by [NixCmd::supports]
+    pub fn with_version(&mut self, version: NixVersion) {
+        self.version = Some(version);
+    }
+
+    /// Whether the `nix` command has the given [Capability]
+    ///
+    /// Decided by [NixCmd::version] when known; otherwise, by looking for the
+    /// capability in the `--help` output of `nix`.
+    pub async fn supports(&self, capability: Capability) -> bool {
+        if let Some(version) = &self.version {
+            return capability.supported_by(version);
+        }
+        let args = [capability.help_args(), &["--help"]].concat();
+        match self.run_with_args_returning_stdout(&args).await {
+            Ok(help) => capability.supported_in_help(&String::from_utf8_lossy(&help)),
+            Err(err) => {
+                tracing::debug!("Assuming `{}` is unsupported: {}", capability, err);
+                false
+            }
+        }
+    }
+
+    /// Execute commands using the given [CommandRunner]
+    ///
+    /// Only the `run_with_args*` functions that wait for the command to finish
+    /// go through the runner; the [Command] returned by [NixCmd::command] is
+    /// always a real process.
+    pub fn with_runner(&mut self, runner: impl CommandRunner + 'static) {
+        self.runner = Runner::new(runner);
+    }
+
     /// Return a [Command] for this [NixCmd] configuration
     pub fn command(&self) -> Command {
         let mut cmd = Command::new(self.command.as_deref().unwrap_or("nix"));
@@ -135,7 +203,7 @@ impl NixCmd {
         let mut cmd = self.command();
         cmd.args(args);
         trace_cmd(&cmd);
-        let out = cmd.output().await?;
+        let out = self.runner.get().output(&mut cmd).await?;
         if out.status.success() {
             Ok(out.stdout)
         } else {
@@ -152,7 +220,7 @@ impl NixCmd {
         let mut cmd = self.command();
         cmd.args(args);
         trace_cmd(&cmd);
-        let status = cmd.spawn()?.wait().await?;
+        let status = self.runner.get().status(&mut cmd).await?;
         if status.success() {
             Ok(())
         } else {
@@ -163,6 +231,30 @@ impl NixCmd {
         }
     }
 
+    /// Run nix with given args, using `--log-format internal-json`, and return
+    /// the spawned process along with a [Stream] of its parsed stderr.
+    ///
+    /// The stdout of the process is piped. It is up to the caller to consume
+    /// the stream and wait on the process.
+    pub fn run_with_args_streaming_log(
+        &self,
+        args: &[&str],
+    ) -> Result<(Child, impl Stream<Item = Result<LogLine, LogError>>), CommandError> {
+        let mut cmd = self.command();
+        cmd.args(["--log-format", "internal-json"]);
+        cmd.args(args);
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        trace_cmd(&cmd);
+        let mut child = cmd.spawn()?;
+        let stderr = child
+            .stderr
+            .take()
+            .expect("stderr is piped, and thus available");
+        Ok((child, crate::log::log_stream(stderr)))
+    }
+
     /// Convert this [NixCmd] configuration into a list of arguments for
     /// [Command]
     fn args(&self) -> Vec<String> {
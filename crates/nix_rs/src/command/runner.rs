@@ -0,0 +1,318 @@
+//! Pluggable execution of the commands built by [super::NixCmd]
+//!
+//! By default, commands are run as real processes ([ProcessRunner]). Tests can
+//! instead use [ReplayRunner] to replay the outputs previously captured by
+//! [RecordingRunner], thus requiring neither Nix nor network access.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use nix_rs::command::{runner::ReplayRunner, NixCmd};
+//! let mut cmd = NixCmd::default();
+//! cmd.with_runner(ReplayRunner::from_file("fixtures/nix.json")?);
+//! let version = nix_rs::version::NixVersion::from_nix(&cmd).await?;
+//! ```
+use std::{
+    fmt,
+    future::Future,
+    hash::{Hash, Hasher},
+    io::Write,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    pin::Pin,
+    process::{ExitStatus, Output},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+/// A boxed future returned by [CommandRunner] methods
+pub type RunnerFuture<'a, T> = Pin<Box<dyn Future<Output = std::io::Result<T>> + Send + 'a>>;
+
+/// Something that can execute a [Command]
+pub trait CommandRunner: fmt::Debug + Send + Sync {
+    /// Run the command to completion, capturing its stdout and stderr
+    fn output<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, Output>;
+
+    /// Run the command to completion, letting it inherit the parent's stdout
+    /// and stderr
+    fn status<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, ExitStatus>;
+}
+
+/// The default [CommandRunner], which spawns real processes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessRunner;
+
+impl CommandRunner for ProcessRunner {
+    fn output<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, Output> {
+        Box::pin(cmd.output())
+    }
+
+    fn status<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, ExitStatus> {
+        Box::pin(async move { cmd.spawn()?.wait().await })
+    }
+}
+
+/// The outcome of a command invocation, as captured by [RecordingRunner]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    /// Arguments passed to the program (the program itself is not included,
+    /// as its path is often machine-specific)
+    pub args: Vec<String>,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+impl Recording {
+    fn exit_status(&self) -> ExitStatus {
+        // The raw value is in the format returned by `waitpid`
+        ExitStatus::from_raw(self.exit_code << 8)
+    }
+
+    fn to_output(&self) -> Output {
+        Output {
+            status: self.exit_status(),
+            stdout: self.stdout.as_bytes().to_vec(),
+            stderr: self.stderr.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// A [CommandRunner] that delegates to another runner, capturing every
+/// invocation as a [Recording]
+#[derive(Debug)]
+pub struct RecordingRunner {
+    inner: Arc<dyn CommandRunner>,
+    recordings: Mutex<Vec<Recording>>,
+}
+
+impl Default for RecordingRunner {
+    /// Record the invocations of real processes
+    fn default() -> Self {
+        Self::new(ProcessRunner)
+    }
+}
+
+impl RecordingRunner {
+    pub fn new(inner: impl CommandRunner + 'static) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            recordings: Mutex::new(vec![]),
+        }
+    }
+
+    /// Return the invocations recorded so far
+    pub fn recordings(&self) -> Vec<Recording> {
+        self.recordings.lock().unwrap().clone()
+    }
+
+    /// Write the invocations recorded so far to a JSON file that can be read
+    /// by [ReplayRunner::from_file]
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.recordings())?;
+        std::fs::write(path, json)
+    }
+
+    fn record(&self, recording: Recording) {
+        self.recordings.lock().unwrap().push(recording);
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn output<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, Output> {
+        Box::pin(async move {
+            let args = get_args(cmd);
+            let out = self.inner.output(cmd).await?;
+            self.record(Recording {
+                args,
+                stdout: String::from_utf8_lossy(&out.stdout).to_string(),
+                stderr: String::from_utf8_lossy(&out.stderr).to_string(),
+                exit_code: out.status.code().unwrap_or(1),
+            });
+            Ok(out)
+        })
+    }
+
+    fn status<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, ExitStatus> {
+        Box::pin(async move {
+            let args = get_args(cmd);
+            let status = self.inner.status(cmd).await?;
+            // The output went to the parent process, so it cannot be captured.
+            self.record(Recording {
+                args,
+                stdout: String::new(),
+                stderr: String::new(),
+                exit_code: status.code().unwrap_or(1),
+            });
+            Ok(status)
+        })
+    }
+}
+
+/// A [CommandRunner] that replays [Recording]s instead of running anything
+///
+/// Invocations are matched on their arguments. An invocation without a
+/// matching recording fails with [std::io::ErrorKind::NotFound].
+#[derive(Debug, Clone, Default)]
+pub struct ReplayRunner {
+    recordings: Vec<Recording>,
+}
+
+impl ReplayRunner {
+    pub fn new(recordings: Vec<Recording>) -> Self {
+        Self { recordings }
+    }
+
+    /// Read recordings from a JSON file written by [RecordingRunner::save]
+    pub fn from_file(path: &Path) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        let recordings = serde_json::from_str(&json)?;
+        Ok(Self::new(recordings))
+    }
+
+    fn lookup(&self, cmd: &Command) -> std::io::Result<&Recording> {
+        let args = get_args(cmd);
+        self.recordings
+            .iter()
+            .find(|r| r.args == args)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No recording for command: {}", super::to_cli(cmd)),
+                )
+            })
+    }
+}
+
+impl CommandRunner for ReplayRunner {
+    fn output<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, Output> {
+        Box::pin(async move { Ok(self.lookup(cmd)?.to_output()) })
+    }
+
+    fn status<'a>(&'a self, cmd: &'a mut Command) -> RunnerFuture<'a, ExitStatus> {
+        Box::pin(async move {
+            let recording = self.lookup(cmd)?;
+            // Mimic a process inheriting the parent's stdout and stderr
+            std::io::stdout().write_all(recording.stdout.as_bytes())?;
+            std::io::stderr().write_all(recording.stderr.as_bytes())?;
+            Ok(recording.exit_status())
+        })
+    }
+}
+
+/// The [CommandRunner] used by a [super::NixCmd]
+///
+/// Two values are equal only if they share the same runner instance, or if
+/// both use the default [ProcessRunner].
+#[derive(Clone, Default)]
+pub struct Runner(Option<Arc<dyn CommandRunner>>);
+
+impl Runner {
+    pub fn new(runner: impl CommandRunner + 'static) -> Self {
+        Self(Some(Arc::new(runner)))
+    }
+
+    /// Get the underlying [CommandRunner]
+    pub fn get(&self) -> &dyn CommandRunner {
+        match &self.0 {
+            Some(runner) => runner.as_ref(),
+            None => &ProcessRunner,
+        }
+    }
+}
+
+impl fmt::Debug for Runner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+impl PartialEq for Runner {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (None, None) => true,
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Runner {}
+
+impl Hash for Runner {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.is_some().hash(state);
+    }
+}
+
+/// Get the arguments (excluding the program) of a [Command]
+fn get_args(cmd: &Command) -> Vec<String> {
+    cmd.as_std()
+        .get_args()
+        .map(|s| s.to_string_lossy().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay_runner() -> ReplayRunner {
+        ReplayRunner::new(vec![
+            Recording {
+                args: vec!["--version".to_string()],
+                stdout: "nix (Nix) 2.18.1\n".to_string(),
+                stderr: String::new(),
+                exit_code: 0,
+            },
+            Recording {
+                args: vec!["eval".to_string(), "--json".to_string()],
+                stdout: String::new(),
+                stderr: "error: no expression given\n".to_string(),
+                exit_code: 1,
+            },
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_replay_output() {
+        let runner = replay_runner();
+        let mut cmd = Command::new("nix");
+        cmd.arg("--version");
+        let out = runner.output(&mut cmd).await.unwrap();
+        assert!(out.status.success());
+        assert_eq!(out.stdout, b"nix (Nix) 2.18.1\n");
+
+        let mut cmd = Command::new("nix");
+        cmd.args(["eval", "--json"]);
+        let out = runner.output(&mut cmd).await.unwrap();
+        assert_eq!(out.status.code(), Some(1));
+        assert_eq!(out.stderr, b"error: no expression given\n");
+    }
+
+    #[tokio::test]
+    async fn test_replay_missing() {
+        let mut cmd = Command::new("nix");
+        cmd.arg("build");
+        let err = replay_runner().output(&mut cmd).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let recorder = RecordingRunner::new(replay_runner());
+        let mut cmd = Command::new("nix");
+        cmd.args(["eval", "--json"]);
+        let status = recorder.status(&mut cmd).await.unwrap();
+        assert_eq!(status.code(), Some(1));
+        let mut cmd = Command::new("nix");
+        cmd.arg("--version");
+        recorder.output(&mut cmd).await.unwrap();
+
+        let replayer = ReplayRunner::new(recorder.recordings());
+        let out = replayer.output(&mut cmd).await.unwrap();
+        assert_eq!(out.stdout, b"nix (Nix) 2.18.1\n");
+    }
+}
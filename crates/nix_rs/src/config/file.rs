@@ -0,0 +1,373 @@
+//! Parser for `nix.conf` files, to read the Nix configuration without running `nix`
+//!
+//! See <https://nixos.org/manual/nix/stable/command-ref/conf-file>
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum depth of nested `include` directives, to guard against include cycles
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Where the value of a Nix setting came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConfigSource {
+    /// The system `nix.conf` (or a file included by it)
+    SystemFile(PathBuf),
+    /// A user `nix.conf` (or a file included by it)
+    UserFile(PathBuf),
+    /// The `NIX_CONFIG` environment variable
+    Env,
+    /// Options passed to the `nix` command
+    CommandLine,
+    /// Not set anywhere; this is the default value of Nix
+    Default,
+}
+
+impl ConfigSource {
+    /// The source of settings in `path`, when included from this source
+    fn include(&self, path: PathBuf) -> ConfigSource {
+        match self {
+            ConfigSource::SystemFile(_) => ConfigSource::SystemFile(path),
+            _ => ConfigSource::UserFile(path),
+        }
+    }
+
+    /// The file defining the setting, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigSource::SystemFile(path) | ConfigSource::UserFile(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ConfigSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::SystemFile(path) | ConfigSource::UserFile(path) => {
+                write!(f, "{}", path.display())
+            }
+            ConfigSource::Env => write!(f, "$NIX_CONFIG"),
+            ConfigSource::CommandLine => write!(f, "command line"),
+            ConfigSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// A setting as defined across the configuration files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    /// The value set by `key = value`, if any
+    pub value: Option<String>,
+    /// Values appended by `extra-key = value` after the last `key = value`
+    pub extra: Vec<String>,
+    /// Where this setting was last defined
+    pub source: ConfigSource,
+}
+
+impl ConfigEntry {
+    /// The effective value, given the value Nix uses when the setting is not set
+    pub fn resolve(&self, default: &str) -> String {
+        std::iter::once(self.value.as_deref().unwrap_or(default))
+            .chain(self.extra.iter().map(|s| s.as_str()))
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Errors when reading `nix.conf` files
+#[derive(thiserror::Error, Debug)]
+pub enum NixConfError {
+    #[error("Unable to read {}: {err}", path.display())]
+    IOError {
+        path: PathBuf,
+        #[source]
+        err: std::io::Error,
+    },
+
+    #[error("Illegal configuration line '{line}' in {origin}")]
+    IllegalLine { line: String, origin: ConfigSource },
+
+    #[error("File {} included from {origin} not found", path.display())]
+    IncludeNotFound { path: PathBuf, origin: ConfigSource },
+
+    #[error("Includes nested too deeply in {0}")]
+    IncludeDepth(ConfigSource),
+
+    #[error("Invalid value '{value}' for setting '{key}'")]
+    InvalidValue { key: String, value: String },
+}
+
+/// The settings defined in `nix.conf` files, keyed by setting name
+///
+/// `extra-` prefixed settings are merged into the setting they extend.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NixConf {
+    pub entries: BTreeMap<String, ConfigEntry>,
+}
+
+impl NixConf {
+    /// Read the configuration from the same locations as Nix does
+    ///
+    /// In order of increasing precedence,
+    /// - `$NIX_CONF_DIR/nix.conf` (`/etc/nix/nix.conf` by default),
+    /// - the files in `$NIX_USER_CONF_FILES` (colon-separated), or if unset,
+    ///   `nix/nix.conf` under `$XDG_CONFIG_DIRS` and `$XDG_CONFIG_HOME`,
+    /// - the contents of `$NIX_CONFIG`.
+    ///
+    /// Missing files are ignored.
+    pub fn from_env() -> Result<Self, NixConfError> {
+        let mut conf = NixConf::default();
+        let conf_dir = std::env::var("NIX_CONF_DIR").unwrap_or_else(|_| "/etc/nix".to_string());
+        let system_conf = Path::new(&conf_dir).join("nix.conf");
+        conf.apply_file(ConfigSource::SystemFile(system_conf), true, 0)?;
+        // Highest priority first, so apply in reverse
+        for path in user_conf_files().into_iter().rev() {
+            conf.apply_file(ConfigSource::UserFile(path), true, 0)?;
+        }
+        if let Ok(contents) = std::env::var("NIX_CONFIG") {
+            let cwd = std::env::current_dir().unwrap_or_default();
+            conf.apply(&contents, &ConfigSource::Env, &cwd, 0)?;
+        }
+        Ok(conf)
+    }
+
+    /// Read the given system `nix.conf` file, along with the files it includes
+    pub fn from_file(path: &Path) -> Result<Self, NixConfError> {
+        let mut conf = NixConf::default();
+        conf.apply_file(ConfigSource::SystemFile(path.to_path_buf()), false, 0)?;
+        Ok(conf)
+    }
+
+    /// Parse `nix.conf` contents, resolving relative includes against `base_dir`
+    pub fn parse(
+        contents: &str,
+        source: ConfigSource,
+        base_dir: &Path,
+    ) -> Result<Self, NixConfError> {
+        let mut conf = NixConf::default();
+        conf.apply(contents, &source, base_dir, 0)?;
+        Ok(conf)
+    }
+
+    /// Get the setting by the given name
+    pub fn get(&self, key: &str) -> Option<&ConfigEntry> {
+        self.entries.get(key)
+    }
+
+    /// The effective value of the given setting, falling back to `default` if
+    /// it is not set.
+    pub fn value(&self, key: &str, default: &str) -> String {
+        match self.get(key) {
+            Some(entry) => entry.resolve(default),
+            None => default.to_string(),
+        }
+    }
+
+    /// Where the given setting was last defined
+    pub fn source(&self, key: &str) -> Option<&ConfigSource> {
+        self.get(key).map(|entry| &entry.source)
+    }
+
+    /// Apply the file given by `source`, which must be a file source
+    fn apply_file(
+        &mut self,
+        source: ConfigSource,
+        ignore_missing: bool,
+        depth: usize,
+    ) -> Result<(), NixConfError> {
+        let path = source.path().unwrap_or(Path::new("/"));
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if ignore_missing && err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(())
+            }
+            Err(err) => {
+                return Err(NixConfError::IOError {
+                    path: path.to_path_buf(),
+                    err,
+                })
+            }
+        };
+        let base_dir = path.parent().unwrap_or(Path::new("/"));
+        self.apply(&contents, &source, base_dir, depth)
+    }
+
+    fn apply(
+        &mut self,
+        contents: &str,
+        source: &ConfigSource,
+        base_dir: &Path,
+        depth: usize,
+    ) -> Result<(), NixConfError> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(NixConfError::IncludeDepth(source.clone()));
+        }
+        for line in logical_lines(contents) {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.as_slice() {
+                [] => {}
+                [directive @ ("include" | "!include"), path] => {
+                    let path = base_dir.join(path);
+                    if *directive == "include" && !path.exists() {
+                        return Err(NixConfError::IncludeNotFound {
+                            path,
+                            origin: source.clone(),
+                        });
+                    }
+                    self.apply_file(source.include(path), true, depth + 1)?;
+                }
+                [key, "=", value @ ..] => self.set(key, value.join(" "), source),
+                _ => {
+                    return Err(NixConfError::IllegalLine {
+                        line: line.trim().to_string(),
+                        origin: source.clone(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: String, source: &ConfigSource) {
+        let (key, is_extra) = match key.strip_prefix("extra-") {
+            Some(key) => (key, true),
+            None => (key, false),
+        };
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| ConfigEntry {
+                value: None,
+                extra: vec![],
+                source: source.clone(),
+            });
+        if is_extra {
+            entry.extra.push(value);
+        } else {
+            entry.value = Some(value);
+            entry.extra.clear();
+        }
+        entry.source = source.clone();
+    }
+}
+
+/// Split into lines, joining lines ending in a backslash with the next, and
+/// stripping `#` comments.
+fn logical_lines(contents: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut curr = String::new();
+    for line in contents.lines() {
+        let line = line.split_once('#').map_or(line, |(before, _)| before);
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                curr.push_str(line);
+                curr.push(' ');
+            }
+            None => {
+                curr.push_str(line);
+                lines.push(std::mem::take(&mut curr));
+            }
+        }
+    }
+    if !curr.is_empty() {
+        lines.push(curr);
+    }
+    lines
+}
+
+/// The user configuration files, in order of decreasing precedence
+fn user_conf_files() -> Vec<PathBuf> {
+    if let Ok(files) = std::env::var("NIX_USER_CONF_FILES") {
+        return std::env::split_paths(&files).collect();
+    }
+    let config_home = std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| Path::new(&home).join(".config")));
+    let config_dirs = std::env::var("XDG_CONFIG_DIRS").unwrap_or_else(|_| "/etc/xdg".to_string());
+    config_home
+        .into_iter()
+        .chain(std::env::split_paths(&config_dirs))
+        .map(|dir| dir.join("nix").join("nix.conf"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> NixConf {
+        NixConf::parse(contents, ConfigSource::Env, Path::new("/nonexistent")).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let conf = parse(
+            r#"
+# A comment
+max-jobs = auto # trailing comment
+substituters = https://cache.nixos.org/ \
+  https://nix-community.cachix.org
+extra-substituters = https://cache.garnix.io
+experimental-features = nix-command
+experimental-features = nix-command flakes
+extra-trusted-users = srid
+!include nonexistent.conf
+"#,
+        );
+        assert_eq!(conf.value("max-jobs", "1"), "auto");
+        assert_eq!(
+            conf.value("substituters", ""),
+            "https://cache.nixos.org/ https://nix-community.cachix.org https://cache.garnix.io"
+        );
+        assert_eq!(
+            conf.value("experimental-features", ""),
+            "nix-command flakes"
+        );
+        assert_eq!(conf.value("trusted-users", "root"), "root srid");
+        assert_eq!(conf.value("cores", "0"), "0");
+        assert_eq!(conf.source("max-jobs"), Some(&ConfigSource::Env));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |s: &str| NixConf::parse(s, ConfigSource::Env, Path::new("/nonexistent"));
+        assert!(matches!(
+            parse("max-jobs auto"),
+            Err(NixConfError::IllegalLine { .. })
+        ));
+        assert!(matches!(
+            parse("include nonexistent.conf"),
+            Err(NixConfError::IncludeNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = std::env::temp_dir().join(format!("nix-rs-conf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("nix.conf"),
+            "cores = 4\ninclude extra.conf\nextra-trusted-users = bob\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("extra.conf"), "cores = 8\ntrusted-users = alice\n").unwrap();
+        let conf = NixConf::from_file(&dir.join("nix.conf")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(conf.value("cores", "0"), "8");
+        assert_eq!(
+            conf.source("cores"),
+            Some(&ConfigSource::SystemFile(dir.join("extra.conf")))
+        );
+        assert_eq!(conf.value("trusted-users", "root"), "alice bob");
+        assert_eq!(
+            conf.source("trusted-users"),
+            Some(&ConfigSource::SystemFile(dir.join("nix.conf")))
+        );
+    }
+}
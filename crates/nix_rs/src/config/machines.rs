@@ -0,0 +1,249 @@
+//! Remote builders, as configured by the `builders` setting (or `/etc/nix/machines`)
+//!
+//! See <https://nix.dev/manual/nix/stable/advanced-topics/distributed-builds>
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::flake::system::System;
+
+use super::NixConfig;
+
+/// The value of `builders` when it is not set
+pub const DEFAULT_BUILDERS: &str = "@/etc/nix/machines";
+
+/// A remote builder machine
+///
+/// Corresponds to one line of the machines file:
+/// ```text
+/// ssh://mac x86_64-darwin,aarch64-darwin /root/.ssh/id_mac 8 2 big-parallel - -
+/// ```
+/// Fields after the URI are optional, and `-` stands for the default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Machine {
+    /// Store URI of the machine (eg: `ssh://mac` or `ssh-ng://builder@host`)
+    pub uri: String,
+    /// Systems the machine builds for; empty means the local system
+    pub systems: Vec<System>,
+    /// SSH identity file used to connect
+    pub ssh_key: Option<PathBuf>,
+    /// Maximum number of builds to run in parallel on the machine
+    pub max_jobs: Option<u32>,
+    /// Relative speed of the machine, used to prefer faster machines
+    pub speed_factor: Option<u32>,
+    /// Features the machine supports (eg: `kvm`, `big-parallel`)
+    pub supported_features: Vec<String>,
+    /// Features that derivations must require to be built on the machine
+    pub mandatory_features: Vec<String>,
+    /// Base64-encoded public host key of the machine
+    pub public_host_key: Option<String>,
+}
+
+/// Errors when parsing a [Machine]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum MachineError {
+    #[error("Unable to read machines file {}: {err}", path.display())]
+    IOError { path: PathBuf, err: String },
+
+    #[error("Unsupported store URI '{0}' (expected ssh:// or ssh-ng://)")]
+    UnsupportedUri(String),
+
+    #[error("Invalid {field} '{value}' in builder '{line}'")]
+    InvalidField {
+        line: String,
+        field: &'static str,
+        value: String,
+    },
+
+    #[error("Too many fields in builder '{0}'")]
+    TooManyFields(String),
+}
+
+impl FromStr for Machine {
+    type Err = MachineError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() > 8 {
+            return Err(MachineError::TooManyFields(line.to_string()));
+        }
+        // `-` (or a missing field) means the default value
+        let field = |i: usize| fields.get(i).copied().filter(|s| *s != "-");
+        let list = |i: usize| -> Vec<String> {
+            field(i)
+                .map(|s| s.split(',').map(|s| s.to_string()).collect())
+                .unwrap_or_default()
+        };
+        let number = |i: usize, name: &'static str| -> Result<Option<u32>, MachineError> {
+            field(i)
+                .map(|s| {
+                    s.parse().map_err(|_| MachineError::InvalidField {
+                        line: line.to_string(),
+                        field: name,
+                        value: s.to_string(),
+                    })
+                })
+                .transpose()
+        };
+
+        let uri = fields.first().copied().unwrap_or_default();
+        match uri.split_once("://") {
+            Some(("ssh" | "ssh-ng", _)) => {}
+            // A bare host name means `ssh://host`
+            None if !uri.is_empty() => {}
+            _ => return Err(MachineError::UnsupportedUri(uri.to_string())),
+        }
+        Ok(Machine {
+            uri: uri.to_string(),
+            systems: list(1).into_iter().map(System::from).collect(),
+            ssh_key: field(2).map(PathBuf::from),
+            max_jobs: number(3, "max jobs")?,
+            speed_factor: number(4, "speed factor")?,
+            supported_features: list(5),
+            mandatory_features: list(6),
+            public_host_key: field(7).map(|s| s.to_string()),
+        })
+    }
+}
+
+impl Display for Machine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let or_dash = |s: String| if s.is_empty() { "-".to_string() } else { s };
+        let systems = self
+            .systems
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {}",
+            self.uri,
+            or_dash(systems),
+            or_dash(
+                self.ssh_key
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default()
+            ),
+            or_dash(self.max_jobs.map(|n| n.to_string()).unwrap_or_default()),
+            or_dash(self.speed_factor.map(|n| n.to_string()).unwrap_or_default()),
+            or_dash(self.supported_features.join(",")),
+            or_dash(self.mandatory_features.join(",")),
+            or_dash(self.public_host_key.clone().unwrap_or_default()),
+        )
+    }
+}
+
+impl Machine {
+    /// The host name of the machine
+    pub fn host(&self) -> &str {
+        let host = self
+            .uri
+            .split_once("://")
+            .map_or(self.uri.as_str(), |(_, rest)| rest);
+        let host = host.split(['/', '?']).next().unwrap_or(host);
+        host.rsplit_once('@').map_or(host, |(_, host)| host)
+    }
+}
+
+/// Parse the value of the `builders` setting
+///
+/// Machines are separated by newlines or `;`. An entry of the form `@path`
+/// reads machines from that file; a missing file has no machines.
+pub fn parse_builders(spec: &str) -> Vec<Result<Machine, MachineError>> {
+    parse_builders_at_depth(spec, 0)
+}
+
+fn parse_builders_at_depth(spec: &str, depth: usize) -> Vec<Result<Machine, MachineError>> {
+    spec.split(['\n', ';'])
+        .map(|line| {
+            line.split_once('#')
+                .map_or(line, |(before, _)| before)
+                .trim()
+        })
+        .filter(|line| !line.is_empty())
+        .flat_map(|line| match line.strip_prefix('@') {
+            // Machines files may not refer to each other indefinitely
+            Some(path) if depth < 8 => read_machines_file(Path::new(path), depth + 1),
+            Some(_) => vec![],
+            None => vec![line.parse()],
+        })
+        .collect()
+}
+
+fn read_machines_file(path: &Path, depth: usize) -> Vec<Result<Machine, MachineError>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => parse_builders_at_depth(&contents, depth),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(err) => vec![Err(MachineError::IOError {
+            path: path.to_path_buf(),
+            err: err.to_string(),
+        })],
+    }
+}
+
+impl NixConfig {
+    /// The remote builders configured by the `builders` setting
+    pub fn builders(&self) -> Vec<Result<Machine, MachineError>> {
+        let spec = self
+            .get_string("builders")
+            .unwrap_or_else(|| DEFAULT_BUILDERS.to_string());
+        parse_builders(&spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_machine() {
+        let m: Machine =
+            "ssh://builder@mac x86_64-darwin,aarch64-darwin /root/.ssh/id_mac 8 - big-parallel"
+                .parse()
+                .unwrap();
+        assert_eq!(m.host(), "mac");
+        assert_eq!(
+            m.systems,
+            vec![
+                System::from("x86_64-darwin"),
+                System::from("aarch64-darwin")
+            ]
+        );
+        assert_eq!(m.ssh_key, Some(PathBuf::from("/root/.ssh/id_mac")));
+        assert_eq!(m.max_jobs, Some(8));
+        assert_eq!(m.speed_factor, None);
+        assert_eq!(m.supported_features, vec!["big-parallel".to_string()]);
+        assert!(m.mandatory_features.is_empty());
+        assert_eq!(
+            m.to_string(),
+            "ssh://builder@mac x86_64-darwin,aarch64-darwin /root/.ssh/id_mac 8 - big-parallel - -"
+        );
+
+        let m: Machine = "linux-builder".parse().unwrap();
+        assert_eq!(m.host(), "linux-builder");
+        assert!(m.systems.is_empty());
+    }
+
+    #[test]
+    fn test_parse_builders() {
+        let builders = parse_builders(
+            "ssh://a aarch64-linux ; ssh-ng://b x86_64-linux - many\nhttps://c\n@/nonexistent/machines",
+        );
+        assert_eq!(builders.len(), 3);
+        assert!(builders[0].is_ok());
+        assert!(matches!(
+            builders[1],
+            Err(MachineError::InvalidField {
+                field: "max jobs",
+                ..
+            })
+        ));
+        assert!(matches!(builders[2], Err(MachineError::UnsupportedUri(_))));
+    }
+}
@@ -0,0 +1,573 @@
+//! Rust module for `nix show-config`
+pub mod file;
+pub mod machines;
+
+use std::{collections::BTreeMap, convert::Infallible, str::FromStr};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use tokio::sync::OnceCell;
+use tracing::instrument;
+use url::Url;
+
+use crate::command::{capability::Capability, NixCmd, NixCmdError};
+
+use super::flake::system::System;
+use file::{ConfigSource, NixConf, NixConfError};
+
+/// Nix configuration spit out by `nix show-config`
+///
+/// Commonly used settings are available as typed fields; use the accessors
+/// (eg: [NixConfig::get_bool]) for the rest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "ConfigSettings", into = "ConfigSettings")]
+pub struct NixConfig {
+    pub cores: ConfigVal<i32>,
+    pub experimental_features: ConfigVal<Vec<String>>,
+    pub extra_platforms: ConfigVal<Vec<String>>,
+    pub flake_registry: ConfigVal<String>,
+    pub max_jobs: ConfigVal<i32>,
+    pub substituters: ConfigVal<Vec<Url>>,
+    pub system: ConfigVal<System>,
+    pub trusted_users: ConfigVal<Vec<TrustedUserValue>>,
+    /// Every setting (including the above), keyed by name
+    pub settings: ConfigSettings,
+}
+
+/// All settings in `nix show-config --json`, keyed by name
+pub type ConfigSettings = BTreeMap<String, ConfigVal<serde_json::Value>>;
+
+/// The value for each 'nix show-config --json' key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigVal<T> {
+    /// Current value in use.
+    pub value: T,
+    /// Default value by Nix.
+    pub default_value: T,
+    /// Description of this config item.
+    pub description: String,
+    /// Where the current value came from, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ConfigSource>,
+}
+
+static NIX_CONFIG: OnceCell<Result<NixConfig, NixCmdError>> = OnceCell::const_new();
+
+impl NixConfig {
+    /// Get the once version of `NixConfig`.
+    ///
+    /// If `nix show-config` fails (eg: on a broken Nix install), the
+    /// configuration is read from the `nix.conf` files instead.
+    #[instrument(name = "show-config(once)")]
+    pub async fn get() -> &'static Result<NixConfig, NixCmdError> {
+        NIX_CONFIG
+            .get_or_init(|| async {
+                let mut cmd = NixCmd::default();
+                cmd.with_flakes(); // Enable flakes, since don't yet know if it is already enabled.
+                match NixConfig::from_nix(&cmd).await {
+                    Ok(cfg) => Ok(cfg),
+                    Err(err) => match NixConfig::from_conf_files() {
+                        Ok(cfg) => {
+                            tracing::warn!(
+                                "`nix show-config` failed; using nix.conf files instead: {}",
+                                err
+                            );
+                            Ok(cfg)
+                        }
+                        Err(_) => Err(err),
+                    },
+                }
+            })
+            .await
+    }
+
+    /// Get the output of `nix config show` (or `nix show-config` in older
+    /// versions of Nix)
+    ///
+    /// The [ConfigVal::source] of each setting is determined by reading the
+    /// `nix.conf` files.
+    #[instrument(name = "show-config")]
+    pub async fn from_nix(
+        nix_cmd: &super::command::NixCmd,
+    ) -> Result<NixConfig, super::command::NixCmdError> {
+        let args: &[&str] = if nix_cmd.supports(Capability::ConfigShow).await {
+            &["config", "show", "--json"]
+        } else {
+            &["show-config", "--json"]
+        };
+        let mut v: NixConfig = nix_cmd.run_with_args_expecting_json(args).await?;
+        let conf = NixConf::from_env().unwrap_or_else(|err| {
+            tracing::debug!("Unable to read nix.conf files: {}", err);
+            NixConf::default()
+        });
+        v.set_sources(&conf, &command_line_settings(nix_cmd));
+        Ok(v)
+    }
+
+    /// Read the configuration from `nix.conf` files, without running `nix`
+    ///
+    /// See [NixConf::from_env] for the files read.
+    pub fn from_conf_files() -> Result<NixConfig, NixConfError> {
+        NixConfig::from_conf(&NixConf::from_env()?)
+    }
+
+    /// Build the configuration from parsed `nix.conf` settings
+    ///
+    /// Settings not in `conf` take the default value of Nix.
+    pub fn from_conf(conf: &NixConf) -> Result<NixConfig, NixConfError> {
+        let system = current_system();
+        let mut cfg = NixConfig {
+            cores: conf_val(
+                conf,
+                "cores",
+                "0",
+                "Number of CPU cores used per build job.",
+                |s| s.parse().ok(),
+            )?,
+            experimental_features: conf_val(
+                conf,
+                "experimental-features",
+                "",
+                "Experimental features that are enabled.",
+                words,
+            )?,
+            extra_platforms: conf_val(
+                conf,
+                "extra-platforms",
+                "",
+                "Additional system types this machine can build for.",
+                words,
+            )?,
+            flake_registry: conf_val(
+                conf,
+                "flake-registry",
+                "https://channels.nixos.org/flake-registry.json",
+                "Path or URI of the global flake registry.",
+                |s| Some(s.to_string()),
+            )?,
+            max_jobs: conf_val(
+                conf,
+                "max-jobs",
+                "1",
+                "Maximum number of jobs that Nix will try to build in parallel.",
+                |s| match s {
+                    "auto" => std::thread::available_parallelism()
+                        .ok()
+                        .and_then(|n| i32::try_from(n.get()).ok()),
+                    _ => s.parse().ok(),
+                },
+            )?,
+            substituters: conf_val(
+                conf,
+                "substituters",
+                "https://cache.nixos.org/",
+                "URLs of the binary caches.",
+                |s| s.split_whitespace().map(|u| Url::parse(u).ok()).collect(),
+            )?,
+            system: conf_val(
+                conf,
+                "system",
+                system.as_ref(),
+                "The system type of the current Nix installation.",
+                |s| Some(System::from(s)),
+            )?,
+            trusted_users: conf_val(
+                conf,
+                "trusted-users",
+                "root",
+                "Users that have additional rights when connecting to the Nix daemon.",
+                |s| {
+                    s.split_whitespace()
+                        .map(|u| Some(u.to_string().into()))
+                        .collect()
+                },
+            )?,
+            // nix.conf values are untyped; keep them as strings unless they
+            // look like booleans or integers.
+            settings: conf
+                .entries
+                .iter()
+                .map(|(key, entry)| {
+                    let value = entry.resolve("");
+                    let value = match value.as_str() {
+                        "true" => serde_json::Value::Bool(true),
+                        "false" => serde_json::Value::Bool(false),
+                        _ => value
+                            .parse::<i64>()
+                            .map(serde_json::Value::from)
+                            .unwrap_or(serde_json::Value::String(value)),
+                    };
+                    let val = ConfigVal {
+                        value,
+                        default_value: serde_json::Value::Null,
+                        description: String::new(),
+                        source: None,
+                    };
+                    (key.clone(), val)
+                })
+                .collect(),
+        };
+        cfg.sync_settings();
+        cfg.set_sources(conf, &[]);
+        Ok(cfg)
+    }
+
+    /// Set the [ConfigVal::source] of every setting
+    ///
+    /// `command_line` are the settings overridden by options passed to `nix`.
+    fn set_sources(&mut self, conf: &NixConf, command_line: &[&str]) {
+        for (key, val) in self.settings.iter_mut() {
+            val.source = if command_line.contains(&key.as_str()) {
+                Some(ConfigSource::CommandLine)
+            } else if let Some(source) = conf.source(key) {
+                Some(source.clone())
+            } else if val.value == val.default_value {
+                Some(ConfigSource::Default)
+            } else {
+                // Eg: set by the Nix daemon, or a file we could not read
+                None
+            };
+        }
+        let source = |key: &str| self.settings.get(key).and_then(|v| v.source.clone());
+        self.cores.source = source("cores");
+        self.experimental_features.source = source("experimental-features");
+        self.extra_platforms.source = source("extra-platforms");
+        self.flake_registry.source = source("flake-registry");
+        self.max_jobs.source = source("max-jobs");
+        self.substituters.source = source("substituters");
+        self.system.source = source("system");
+        self.trusted_users.source = source("trusted-users");
+    }
+
+    /// Get the given setting
+    pub fn setting(&self, key: &str) -> Option<&ConfigVal<serde_json::Value>> {
+        self.settings.get(key)
+    }
+
+    /// Get the value of the given setting, deserialized as `T`
+    pub fn get_as<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        serde_json::from_value(self.setting(key)?.value.clone()).ok()
+    }
+
+    /// Get the value of a boolean setting (eg: `sandbox`)
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match &self.setting(key)?.value {
+            serde_json::Value::Bool(b) => Some(*b),
+            serde_json::Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the value of an integer setting (eg: `connect-timeout`)
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match &self.setting(key)?.value {
+            serde_json::Value::Number(n) => n.as_i64(),
+            serde_json::Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a string setting (eg: `netrc-file`)
+    pub fn get_string(&self, key: &str) -> Option<String> {
+        match &self.setting(key)?.value {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Null => None,
+            v => Some(v.to_string()),
+        }
+    }
+
+    /// Get the value of a list setting (eg: `trusted-public-keys`)
+    ///
+    /// Nix accepts lists as whitespace-separated strings, so those are split.
+    pub fn get_list(&self, key: &str) -> Option<Vec<String>> {
+        match &self.setting(key)?.value {
+            serde_json::Value::Array(xs) => Some(
+                xs.iter()
+                    .map(|x| match x {
+                        serde_json::Value::String(s) => s.clone(),
+                        x => x.to_string(),
+                    })
+                    .collect(),
+            ),
+            serde_json::Value::String(s) => {
+                Some(s.split_whitespace().map(|w| w.to_string()).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Update [NixConfig::settings] with the values of the typed fields
+    fn sync_settings(&mut self) {
+        let typed = [
+            ("cores", to_json_val(&self.cores)),
+            (
+                "experimental-features",
+                to_json_val(&self.experimental_features),
+            ),
+            ("extra-platforms", to_json_val(&self.extra_platforms)),
+            ("flake-registry", to_json_val(&self.flake_registry)),
+            ("max-jobs", to_json_val(&self.max_jobs)),
+            ("substituters", to_json_val(&self.substituters)),
+            ("system", to_json_val(&self.system)),
+            ("trusted-users", to_json_val(&self.trusted_users)),
+        ];
+        for (key, val) in typed {
+            self.settings.insert(key.to_string(), val);
+        }
+    }
+
+    /// Is flakes and command features enabled?
+    pub fn is_flakes_enabled(&self) -> bool {
+        self.experimental_features
+            .value
+            .contains(&"nix-command".to_string())
+            && self
+                .experimental_features
+                .value
+                .contains(&"flakes".to_string())
+    }
+}
+
+impl TryFrom<ConfigSettings> for NixConfig {
+    type Error = serde_json::Error;
+
+    fn try_from(settings: ConfigSettings) -> Result<Self, Self::Error> {
+        fn typed<T: DeserializeOwned>(
+            settings: &ConfigSettings,
+            key: &'static str,
+        ) -> Result<ConfigVal<T>, serde_json::Error> {
+            let val = settings
+                .get(key)
+                .ok_or_else(|| serde::de::Error::missing_field(key))?;
+            Ok(ConfigVal {
+                value: serde_json::from_value(val.value.clone())?,
+                default_value: serde_json::from_value(val.default_value.clone())?,
+                description: val.description.clone(),
+                source: val.source.clone(),
+            })
+        }
+        Ok(NixConfig {
+            cores: typed(&settings, "cores")?,
+            experimental_features: typed(&settings, "experimental-features")?,
+            extra_platforms: typed(&settings, "extra-platforms")?,
+            flake_registry: typed(&settings, "flake-registry")?,
+            max_jobs: typed(&settings, "max-jobs")?,
+            substituters: typed(&settings, "substituters")?,
+            system: typed(&settings, "system")?,
+            trusted_users: typed(&settings, "trusted-users")?,
+            settings,
+        })
+    }
+}
+
+impl From<NixConfig> for ConfigSettings {
+    fn from(mut cfg: NixConfig) -> Self {
+        cfg.sync_settings();
+        cfg.settings
+    }
+}
+
+/// Convert a typed [ConfigVal] to its JSON form
+fn to_json_val<T: Serialize>(val: &ConfigVal<T>) -> ConfigVal<serde_json::Value> {
+    let to_json = |v: &T| serde_json::to_value(v).unwrap_or_default();
+    ConfigVal {
+        value: to_json(&val.value),
+        default_value: to_json(&val.default_value),
+        description: val.description.clone(),
+        source: val.source.clone(),
+    }
+}
+
+/// The settings overridden by the options of the given `nix` command
+fn command_line_settings(nix_cmd: &NixCmd) -> Vec<&'static str> {
+    let mut keys = vec![];
+    if !nix_cmd.extra_experimental_features.is_empty() {
+        keys.push("experimental-features");
+    }
+    if !nix_cmd.extra_access_tokens.is_empty() {
+        keys.push("access-tokens");
+    }
+    keys
+}
+
+/// Build the [ConfigVal] of a setting in [NixConf]
+fn conf_val<T>(
+    conf: &NixConf,
+    key: &str,
+    default: &str,
+    description: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<ConfigVal<T>, NixConfError> {
+    let parse = |s: &str| {
+        parse(s).ok_or_else(|| NixConfError::InvalidValue {
+            key: key.to_string(),
+            value: s.to_string(),
+        })
+    };
+    Ok(ConfigVal {
+        value: parse(&conf.value(key, default))?,
+        default_value: parse(default)?,
+        description: description.to_string(),
+        source: None,
+    })
+}
+
+fn words(s: &str) -> Option<Vec<String>> {
+    Some(s.split_whitespace().map(|w| w.to_string()).collect())
+}
+
+/// The system of the running machine, as Nix names it (eg: `x86_64-linux`)
+fn current_system() -> System {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    System::from(format!("{}-{}", std::env::consts::ARCH, os))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, SerializeDisplay, DeserializeFromStr)]
+pub enum TrustedUserValue {
+    /// All users are trusted
+    All,
+    /// A specific user is trusted
+    User(String),
+    /// Users belonging to a specific group are trusted
+    Group(String),
+}
+
+impl TrustedUserValue {
+    fn from_str(s: &str) -> Self {
+        // In nix.conf, groups are prefixed with '@'. '*' means all users are
+        // trusted.
+        if s == "*" {
+            return Self::All;
+        }
+        match s.strip_prefix('@') {
+            Some(s) => Self::Group(s.to_string()),
+            None => Self::User(s.to_string()),
+        }
+    }
+
+    pub fn display_original(val: &[TrustedUserValue]) -> String {
+        val.iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+impl std::fmt::Display for TrustedUserValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrustedUserValue::All => write!(f, "*"),
+            TrustedUserValue::User(x) => write!(f, "{}", x),
+            TrustedUserValue::Group(x) => write!(f, "@{}", x),
+        }
+    }
+}
+
+impl From<String> for TrustedUserValue {
+    fn from(s: String) -> Self {
+        Self::from_str(&s)
+    }
+}
+
+impl FromStr for TrustedUserValue {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_str(s))
+    }
+}
+
+#[tokio::test]
+async fn test_nix_config() -> Result<(), crate::command::NixCmdError> {
+    let v = NixConfig::get().await.as_ref().unwrap();
+    println!("Max Jobs: {}", v.max_jobs.value);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_nix_config() {
+    use crate::command::runner::{Recording, ReplayRunner};
+    let stdout = r#"{
+        "cores": {"value": 8, "defaultValue": 0, "description": "..."},
+        "experimental-features": {"value": ["flakes", "nix-command"], "defaultValue": [], "description": "..."},
+        "extra-platforms": {"value": [], "defaultValue": [], "description": "..."},
+        "flake-registry": {"value": "https://channels.nixos.org/flake-registry.json", "defaultValue": "https://channels.nixos.org/flake-registry.json", "description": "..."},
+        "max-jobs": {"value": 1, "defaultValue": 1, "description": "..."},
+        "substituters": {"value": ["https://cache.nixos.org/"], "defaultValue": ["https://cache.nixos.org/"], "description": "..."},
+        "system": {"value": "x86_64-linux", "defaultValue": "x86_64-linux", "description": "..."},
+        "trusted-users": {"value": ["root", "@wheel"], "defaultValue": ["root"], "description": "..."},
+        "sandbox": {"value": true, "defaultValue": true, "description": "..."},
+        "connect-timeout": {"value": 5, "defaultValue": 0, "description": "..."},
+        "netrc-file": {"value": "/etc/nix/netrc", "defaultValue": "/etc/nix/netrc", "description": "..."},
+        "trusted-public-keys": {"value": ["cache.nixos.org-1:6NCHdD59X431o0gWypbMrAURkbJ16ZPMQFGspcDShjY="], "defaultValue": [], "description": "..."}
+    }"#;
+    let mut cmd = NixCmd::default();
+    cmd.with_runner(ReplayRunner::new(vec![Recording {
+        args: vec!["show-config".to_string(), "--json".to_string()],
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+        exit_code: 0,
+    }]));
+    let v = NixConfig::from_nix(&cmd).await.unwrap();
+    assert_eq!(v.max_jobs.value, 1);
+    assert!(v.is_flakes_enabled());
+    assert_eq!(
+        v.trusted_users.value,
+        vec![
+            TrustedUserValue::User("root".to_string()),
+            TrustedUserValue::Group("wheel".to_string())
+        ]
+    );
+    assert_eq!(v.settings.len(), 12);
+    assert_eq!(v.get_bool("sandbox"), Some(true));
+    assert_eq!(v.get_int("connect-timeout"), Some(5));
+    assert_eq!(
+        v.get_string("netrc-file").as_deref(),
+        Some("/etc/nix/netrc")
+    );
+    assert_eq!(v.get_list("trusted-public-keys").map(|k| k.len()), Some(1));
+    assert_eq!(v.get_bool("no-such-setting"), None);
+    // Round-trips through the `nix show-config` format
+    let json = serde_json::to_string(&v).unwrap();
+    assert_eq!(serde_json::from_str::<NixConfig>(&json).unwrap(), v);
+}
+
+#[test]
+fn test_nix_config_from_conf() {
+    let conf = NixConf::parse(
+        "max-jobs = 4\nextra-substituters = https://cache.garnix.io\ntrusted-users = root @wheel\nexperimental-features = nix-command flakes\nsandbox = false\n",
+        ConfigSource::Env,
+        std::path::Path::new("/"),
+    )
+    .unwrap();
+    let v = NixConfig::from_conf(&conf).unwrap();
+    assert_eq!(v.max_jobs.value, 4);
+    assert_eq!(v.max_jobs.default_value, 1);
+    assert!(v.is_flakes_enabled());
+    assert_eq!(v.substituters.value.len(), 2);
+    assert_eq!(v.get_int("max-jobs"), Some(4));
+    assert_eq!(v.get_bool("sandbox"), Some(false));
+    assert_eq!(v.max_jobs.source, Some(ConfigSource::Env));
+    assert_eq!(v.cores.source, Some(ConfigSource::Default));
+    assert_eq!(
+        v.setting("sandbox").and_then(|v| v.source.clone()),
+        Some(ConfigSource::Env)
+    );
+    assert_eq!(v.get_list("substituters").map(|s| s.len()), Some(2));
+    assert_eq!(
+        v.trusted_users.value,
+        vec![
+            TrustedUserValue::User("root".to_string()),
+            TrustedUserValue::Group("wheel".to_string())
+        ]
+    );
+    let bad = NixConf::parse("cores = many", ConfigSource::Env, std::path::Path::new("/")).unwrap();
+    assert!(matches!(
+        NixConfig::from_conf(&bad),
+        Err(NixConfError::InvalidValue { .. })
+    ));
+}
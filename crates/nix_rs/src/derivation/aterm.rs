@@ -0,0 +1,188 @@
+//! Parser for the ATerm serialisation of derivations (the contents of `.drv`
+//! files)
+//!
+//! ```text
+//! Derive([("out","/nix/store/...-hello","","")],[("/nix/store/...-bash.drv",["out"])],["/nix/store/...-builder.sh"],"x86_64-linux","/nix/store/...-bash/bin/bash",["-e","/nix/store/...-builder.sh"],[("name","hello")])
+//! ```
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use crate::{flake::system::System, store::StorePath};
+
+use super::{Derivation, DerivationError, DerivationOutput};
+
+/// Parse a derivation in the ATerm format
+pub fn parse(s: &str) -> Result<Derivation, DerivationError> {
+    let mut p = Parser { s, pos: 0 };
+    p.expect("Derive(")?;
+    let outputs = p.list(|p| {
+        p.expect("(")?;
+        let name = p.string()?;
+        p.expect(",")?;
+        let path = p.string()?;
+        p.expect(",")?;
+        let hash_algo = p.string()?;
+        p.expect(",")?;
+        let hash = p.string()?;
+        p.expect(")")?;
+        let non_empty = |s: String| Some(s).filter(|s| !s.is_empty());
+        let output = DerivationOutput {
+            path: non_empty(path).map(|p| StorePath::new(PathBuf::from(p))),
+            hash_algo: non_empty(hash_algo),
+            hash: non_empty(hash),
+        };
+        Ok((name, output))
+    })?;
+    p.expect(",")?;
+    let input_drvs = p.list(|p| {
+        p.expect("(")?;
+        let drv = p.string()?;
+        p.expect(",")?;
+        let outputs = p.list(Parser::string)?;
+        p.expect(")")?;
+        Ok((StorePath::new(PathBuf::from(drv)), outputs))
+    })?;
+    p.expect(",")?;
+    let input_srcs = p.list(|p| Ok(StorePath::new(PathBuf::from(p.string()?))))?;
+    p.expect(",")?;
+    let system = p.string()?;
+    p.expect(",")?;
+    let builder = p.string()?;
+    p.expect(",")?;
+    let args = p.list(Parser::string)?;
+    p.expect(",")?;
+    let env = p.list(|p| {
+        p.expect("(")?;
+        let k = p.string()?;
+        p.expect(",")?;
+        let v = p.string()?;
+        p.expect(")")?;
+        Ok((k, v))
+    })?;
+    p.expect(")")?;
+    if !p.rest().trim().is_empty() {
+        return Err(p.error("end of input"));
+    }
+    Ok(Derivation {
+        outputs: outputs.into_iter().collect(),
+        input_drvs: input_drvs
+            .into_iter()
+            .map(|(drv, outputs)| (drv, outputs.into_iter().collect::<BTreeSet<_>>()))
+            .collect(),
+        input_srcs: input_srcs.into_iter().collect(),
+        system: System::from(system),
+        builder,
+        args,
+        env: env.into_iter().collect::<BTreeMap<_, _>>(),
+    })
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn error(&self, expected: &'static str) -> DerivationError {
+        DerivationError::Parse {
+            offset: self.pos,
+            expected,
+        }
+    }
+
+    fn expect(&mut self, token: &'static str) -> Result<(), DerivationError> {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error(token))
+        }
+    }
+
+    /// Parse a `[a,b,...]` list, using `item` for the elements
+    fn list<T>(
+        &mut self,
+        item: impl Fn(&mut Self) -> Result<T, DerivationError>,
+    ) -> Result<Vec<T>, DerivationError> {
+        self.expect("[")?;
+        let mut items = vec![];
+        if self.expect("]").is_ok() {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.expect("]").is_ok() {
+                return Ok(items);
+            }
+            self.expect(",").map_err(|_| self.error("`,` or `]`"))?;
+        }
+    }
+
+    /// Parse a double-quoted string, with `\`-escapes
+    fn string(&mut self) -> Result<String, DerivationError> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 'r')) => out.push('\r'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((_, c)) => out.push(c),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        self.pos = self.s.len();
+        Err(self.error("closing `\"`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_escapes() {
+        let drv = parse(r#"Derive([("out","","r:sha256","")],[],[],"x86_64-linux","/bin/sh",["-c","echo \"hi\"\n"],[("a\\b","tab\there")])"#).unwrap();
+        assert_eq!(drv.outputs["out"].path, None);
+        assert_eq!(drv.outputs["out"].hash_algo.as_deref(), Some("r:sha256"));
+        assert_eq!(drv.args[1], "echo \"hi\"\n");
+        assert_eq!(drv.env["a\\b"], "tab\there");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("Derive([],[],[],\"x\",\"b\",[],[])").is_ok());
+        assert!(matches!(
+            parse("Derive([],[]").unwrap_err(),
+            DerivationError::Parse { expected: ",", .. }
+        ));
+        assert!(matches!(
+            parse("Derive([(\"out\",\"p\",\"\",\"\")],[],[],\"x\",\"b\",[],[]) trailing"),
+            Err(DerivationError::Parse {
+                expected: "end of input",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("Derive([(\"out"),
+            Err(DerivationError::Parse {
+                expected: "closing `\"`",
+                ..
+            })
+        ));
+    }
+}
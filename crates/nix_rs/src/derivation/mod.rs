@@ -0,0 +1,233 @@
+//! Rust module for Nix derivations (`.drv` files)
+//!
+//! Derivations are read either with `nix derivation show`
+//! ([Derivation::from_nix]), or directly from their ATerm serialisation on disk
+//! ([Derivation::from_file]).
+pub mod aterm;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::instrument;
+
+use crate::{
+    command::{capability::Capability, NixCmd, NixCmdError},
+    flake::system::System,
+    store::StorePath,
+};
+
+/// A Nix derivation
+///
+/// The serde instances follow the format of `nix derivation show`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Derivation {
+    /// Outputs of the derivation, keyed by output name
+    pub outputs: BTreeMap<String, DerivationOutput>,
+    /// Derivations this derivation depends on, along with the names of the
+    /// outputs used
+    #[serde(deserialize_with = "deserialize_input_drvs")]
+    pub input_drvs: BTreeMap<StorePath, BTreeSet<String>>,
+    /// Store paths (sources) this derivation depends on
+    pub input_srcs: BTreeSet<StorePath>,
+    /// The system the derivation builds on
+    pub system: System,
+    /// The program run to build the derivation
+    pub builder: String,
+    /// Arguments to [Derivation::builder]
+    pub args: Vec<String>,
+    /// Environment variables of the build
+    pub env: BTreeMap<String, String>,
+}
+
+/// An output of a [Derivation]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivationOutput {
+    /// Path of the output; absent for content-addressed derivations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<StorePath>,
+    /// Hash algorithm of fixed-output or content-addressed outputs (eg:
+    /// `sha256`, or `r:sha256` for recursive hashing)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algo: Option<String>,
+    /// Hash of a fixed-output derivation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// Errors when reading a [Derivation]
+#[derive(Error, Debug)]
+pub enum DerivationError {
+    #[error("Unable to read derivation {}: {err}", path.display())]
+    IOError {
+        path: std::path::PathBuf,
+        err: std::io::Error,
+    },
+
+    #[error("Invalid derivation at offset {offset}: expected {expected}")]
+    Parse {
+        offset: usize,
+        expected: &'static str,
+    },
+}
+
+impl Derivation {
+    /// Run `nix derivation show` (or `nix show-derivation` in older versions
+    /// of Nix) on the given installables
+    #[instrument(name = "derivation-show")]
+    pub async fn from_nix(
+        nix_cmd: &NixCmd,
+        installables: &[&str],
+    ) -> Result<BTreeMap<StorePath, Derivation>, NixCmdError> {
+        let cmd: &[&str] = if nix_cmd.supports(Capability::DerivationShow).await {
+            &["derivation", "show"]
+        } else {
+            &["show-derivation"]
+        };
+        let args = [cmd, installables].concat();
+        nix_cmd.run_with_args_expecting_json(&args).await
+    }
+
+    /// Read a `.drv` file in the ATerm format
+    pub fn from_file(path: &Path) -> Result<Derivation, DerivationError> {
+        let s = std::fs::read_to_string(path).map_err(|err| DerivationError::IOError {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        aterm::parse(&s)
+    }
+
+    /// The name of the derivation (eg: `hello-2.12.1`)
+    pub fn name(&self) -> Option<&str> {
+        self.env.get("name").map(|s| s.as_str())
+    }
+
+    /// Whether this is a fixed-output derivation (eg: a source download),
+    /// which has network access, and whose output is known in advance
+    pub fn is_fixed_output(&self) -> bool {
+        self.outputs.values().any(|o| o.hash.is_some())
+    }
+
+    /// The systems the given derivations build on
+    ///
+    /// Derivations building on `builtin` (eg: `builtins.fetchurl`) are ignored,
+    /// as they are built by Nix itself.
+    pub fn systems<'a>(derivations: impl IntoIterator<Item = &'a Derivation>) -> BTreeSet<System> {
+        derivations
+            .into_iter()
+            .map(|d| d.system.clone())
+            .filter(|s| s.as_ref() != "builtin")
+            .collect()
+    }
+}
+
+/// Deserialize `inputDrvs`, which is a map to a list of outputs in older
+/// versions of Nix, and a map to `{ outputs, dynamicOutputs }` in newer ones
+fn deserialize_input_drvs<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<StorePath, BTreeSet<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum InputDrvOutputs {
+        Outputs(BTreeSet<String>),
+        WithDynamic { outputs: BTreeSet<String> },
+    }
+    let m: BTreeMap<StorePath, InputDrvOutputs> = Deserialize::deserialize(deserializer)?;
+    Ok(m.into_iter()
+        .map(|(drv, outputs)| match outputs {
+            InputDrvOutputs::Outputs(outputs) | InputDrvOutputs::WithDynamic { outputs } => {
+                (drv, outputs)
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::command::runner::{Recording, ReplayRunner};
+
+    const HELLO_DRV: &str = "/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv";
+
+    #[tokio::test]
+    async fn test_replay_derivation_show() {
+        let stdout = r#"{
+            "/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv": {
+                "args": ["-e", "/nix/store/v6x3cs394jgqfbi0a42pam708flxaphh-default-builder.sh"],
+                "builder": "/nix/store/5lr5n3qa4day8l1ivbwlcby2nknczqkq-bash-5.2p26/bin/bash",
+                "env": {"name": "hello-2.12.1", "out": "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1", "system": "x86_64-linux"},
+                "inputDrvs": {
+                    "/nix/store/0qcq2d0mgs0rb7d8jpq2dmmhqf1k5j4b-hello-2.12.1.tar.gz.drv": {"dynamicOutputs": {}, "outputs": ["out"]},
+                    "/nix/store/8vb0mhksk9mx4ijnwm8lj6crhzyr7vc0-stdenv-linux.drv": {"dynamicOutputs": {}, "outputs": ["out"]}
+                },
+                "inputSrcs": ["/nix/store/v6x3cs394jgqfbi0a42pam708flxaphh-default-builder.sh"],
+                "name": "hello-2.12.1",
+                "outputs": {"out": {"path": "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1"}},
+                "system": "x86_64-linux"
+            }
+        }"#;
+        let mut cmd = NixCmd::default();
+        cmd.with_version(crate::version::NixVersion::new(2, 24, 0));
+        cmd.with_runner(ReplayRunner::new(vec![Recording {
+            args: ["derivation", "show", "nixpkgs#hello"]
+                .map(String::from)
+                .to_vec(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+        }]));
+        let drvs = Derivation::from_nix(&cmd, &["nixpkgs#hello"])
+            .await
+            .unwrap();
+        let drv = &drvs[&StorePath::new(PathBuf::from(HELLO_DRV))];
+        assert_eq!(drv.name(), Some("hello-2.12.1"));
+        assert_eq!(drv.system, System::from("x86_64-linux"));
+        assert_eq!(drv.input_drvs.len(), 2);
+        assert!(!drv.is_fixed_output());
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/derivation/fixtures");
+        let hello = Derivation::from_file(&dir.join("hello.drv")).unwrap();
+        assert_eq!(hello.name(), Some("hello-2.12.1"));
+        assert_eq!(
+            hello.outputs["out"].path,
+            Some(StorePath::new(PathBuf::from(
+                "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1"
+            )))
+        );
+        assert_eq!(
+            hello.input_drvs.values().flatten().collect::<Vec<_>>(),
+            vec!["out", "out", "out"]
+        );
+        assert_eq!(
+            hello.env["postInstallCheck"],
+            "stat \"${!outputBin}/bin/hello\"\n"
+        );
+        assert_eq!(
+            hello.args,
+            vec![
+                "-e",
+                "/nix/store/v6x3cs394jgqfbi0a42pam708flxaphh-default-builder.sh"
+            ]
+        );
+
+        let src = Derivation::from_file(&dir.join("hello-2.12.1.tar.gz.drv")).unwrap();
+        assert!(src.is_fixed_output());
+        assert_eq!(
+            Derivation::systems([&hello, &src]),
+            BTreeSet::from([System::from("x86_64-linux")])
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! Information about the environment in which Nix will run
+mod user;
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use bytesize::ByteSize;
+use os_info;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::instrument;
+
+use crate::config::file::ConfigSource;
+
+/// Maximum time to spend computing [NixInstall::store_size]
+pub const STORE_SIZE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The environment in which Nix operates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NixEnv {
+    /// Current user ($USER)
+    pub current_user: String,
+    /// Current user groups
+    pub current_user_groups: Vec<String>,
+    /// Underlying OS in which Nix runs
+    pub os: OS,
+    /// Total disk space of the volume where /nix exists.
+    ///
+    /// This is either root volume or the dedicated /nix volume.
+    pub total_disk_space: ByteSize,
+    /// Available disk space on the volume where /nix exists.
+    pub available_disk_space: ByteSize,
+    /// Total memory
+    pub total_memory: ByteSize,
+    /// How Nix is installed
+    pub install: NixInstall,
+}
+
+impl NixEnv {
+    /// Determine [NixEnv] on the user's system
+
+    #[instrument]
+    pub async fn detect() -> Result<NixEnv, NixEnvError> {
+        use sysinfo::{DiskExt, SystemExt};
+        tracing::info!("Detecting Nix environment");
+        let os = OS::detect().await;
+        tokio::task::spawn_blocking(|| {
+            let install = NixInstall::detect(&os);
+            let current_user = user::current_user()?;
+            let sys = sysinfo::System::new_with_specifics(
+                sysinfo::RefreshKind::new().with_disks_list().with_memory(),
+            );
+            let nix_disk = get_nix_disk(&sys)?;
+            let total_disk_space = to_bytesize(nix_disk.total_space());
+            let available_disk_space = to_bytesize(nix_disk.available_space());
+            let total_memory = to_bytesize(sys.total_memory());
+            let current_user_groups = user::current_user_groups()?;
+            Ok(NixEnv {
+                current_user,
+                current_user_groups,
+                os,
+                total_disk_space,
+                available_disk_space,
+                total_memory,
+                install,
+            })
+        })
+        .await
+        .unwrap()
+    }
+}
+
+/// Total size of the files under the given directory, or `None` if that takes
+/// longer than `timeout`
+///
+/// Hard-linked files (eg: due to `auto-optimise-store`) are counted once per link.
+fn dir_size(dir: &Path, timeout: Duration) -> Option<u64> {
+    if !dir.is_dir() {
+        return None;
+    }
+    let deadline = Instant::now() + timeout;
+    let mut total = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if Instant::now() >= deadline {
+            tracing::debug!("Gave up computing the size of {}", dir.display());
+            return None;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += metadata.len();
+            }
+        }
+    }
+    Some(total)
+}
+
+/// Get the disk where /nix exists
+fn get_nix_disk(sys: &sysinfo::System) -> Result<&sysinfo::Disk, NixEnvError> {
+    use sysinfo::{DiskExt, SystemExt};
+    let by_mount_point: std::collections::HashMap<&Path, &sysinfo::Disk> = sys
+        .disks()
+        .iter()
+        .map(|disk| (disk.mount_point(), disk))
+        .collect();
+    // Lookup /nix first, then /.
+    by_mount_point
+        .get(Path::new("/nix"))
+        .copied()
+        .or_else(|| by_mount_point.get(Path::new("/")).copied())
+        .ok_or(NixEnvError::NoDisk)
+}
+
+/// How Nix is installed on this machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NixInstall {
+    /// The installer (or distribution) that installed Nix
+    pub flavor: InstallFlavor,
+    /// Whether this is a multi-user install, where builds are done by the
+    /// Nix daemon (detected from the store's ownership, not the daemon)
+    pub multi_user: bool,
+    /// Whether the Nix daemon is accepting connections
+    pub daemon_running: bool,
+    /// The Nix store (`/nix/store` unless `$NIX_STORE_DIR` is set)
+    pub store_dir: PathBuf,
+    /// The Nix state directory (`/nix/var/nix` unless `$NIX_STATE_DIR` is set)
+    pub state_dir: PathBuf,
+}
+
+/// The installer (or distribution) that installed Nix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstallFlavor {
+    /// Nix is managed by NixOS
+    NixOS,
+    /// Nix is managed by nix-darwin
+    NixDarwin,
+    /// The [Determinate Systems installer](https://github.com/DeterminateSystems/nix-installer)
+    DeterminateSystems,
+    /// The [official installer](https://nixos.org/download)
+    Official,
+    /// A package of the OS distribution (eg: `apt install nix`)
+    DistroPackage,
+    /// None of the above
+    Unknown,
+}
+
+impl Display for InstallFlavor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallFlavor::NixOS => write!(f, "NixOS"),
+            InstallFlavor::NixDarwin => write!(f, "nix-darwin"),
+            InstallFlavor::DeterminateSystems => write!(f, "Determinate Systems installer"),
+            InstallFlavor::Official => write!(f, "official installer"),
+            InstallFlavor::DistroPackage => write!(f, "distribution package"),
+            InstallFlavor::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl Display for NixInstall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {}",
+            self.flavor,
+            if self.multi_user {
+                "multi-user"
+            } else {
+                "single-user"
+            }
+        )?;
+        if self.multi_user && !self.daemon_running {
+            write!(f, " (daemon not running)")?;
+        }
+        Ok(())
+    }
+}
+
+impl NixInstall {
+    /// Detect how Nix is installed
+    ///
+    /// This does blocking IO.
+    pub fn detect(os: &OS) -> NixInstall {
+        let store_dir = crate::store::store_dir();
+        let state_dir = std::env::var("NIX_STATE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("/nix/var/nix"));
+        let socket_dir = state_dir.join("daemon-socket");
+        let daemon_running = is_socket_accepting(&socket_dir.join("socket"));
+        // A multi-user install remains one even when its daemon is down
+        let multi_user = is_store_owned_by_nixbld(&store_dir) || socket_dir.is_dir();
+        let flavor = match os {
+            OS::NixOS => InstallFlavor::NixOS,
+            OS::MacOS {
+                nix_darwin: true, ..
+            } => InstallFlavor::NixDarwin,
+            // The Determinate Systems installer leaves a receipt for uninstallation
+            _ if Path::new("/nix/receipt.json").exists() => InstallFlavor::DeterminateSystems,
+            _ => match find_in_path("nix") {
+                Some(nix) if nix.starts_with(&store_dir) => InstallFlavor::Official,
+                Some(_) => InstallFlavor::DistroPackage,
+                None => InstallFlavor::Unknown,
+            },
+        };
+        NixInstall {
+            flavor,
+            multi_user,
+            daemon_running,
+            store_dir,
+            state_dir,
+        }
+    }
+
+    /// Size of the Nix store
+    ///
+    /// This walks the store, doing blocking IO, and so is not part of
+    /// [NixInstall::detect]. `None` if it could not be computed within
+    /// [STORE_SIZE_TIMEOUT].
+    pub fn store_size(&self) -> Option<ByteSize> {
+        dir_size(&self.store_dir, STORE_SIZE_TIMEOUT).map(to_bytesize)
+    }
+
+    /// The command to restart the Nix daemon, if there is one
+    pub fn daemon_restart_command(&self, os: &OS) -> Option<&'static str> {
+        if !self.multi_user {
+            return None;
+        }
+        match os {
+            OS::MacOS { .. } => Some("sudo launchctl kickstart -k system/org.nixos.nix-daemon"),
+            _ => Some("sudo systemctl restart nix-daemon"),
+        }
+    }
+}
+
+/// Whether the store is owned by root and the `nixbld` group, as in
+/// multi-user installs (a single-user install is owned by its user)
+fn is_store_owned_by_nixbld(store_dir: &Path) -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            use std::os::unix::fs::MetadataExt;
+            std::fs::metadata(store_dir).is_ok_and(|m| {
+                m.uid() == 0 && user::group_name(m.gid()).as_deref() == Some("nixbld")
+            })
+        } else {
+            let _ = store_dir;
+            false
+        }
+    }
+}
+
+/// Whether a process is listening on the given unix socket
+fn is_socket_accepting(path: &Path) -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            std::os::unix::net::UnixStream::connect(path).is_ok()
+        } else {
+            let _ = path;
+            false
+        }
+    }
+}
+
+/// Find the given executable in `$PATH`, resolving symlinks
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|p| p.is_file())
+        .and_then(|p| std::fs::canonicalize(p).ok())
+}
+
+/// The system under which Nix is installed and operates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OS {
+    /// On macOS
+    MacOS {
+        /// Using nix-darwin
+        nix_darwin: bool,
+        arch: MacOSArch,
+    },
+    /// On NixOS
+    NixOS,
+    /// Nix is individually installed on Linux or macOS
+    Other(os_info::Type),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MacOSArch {
+    Arm64(AppleEmulation),
+    Other(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AppleEmulation {
+    None,
+    Rosetta,
+}
+
+impl AppleEmulation {
+    pub fn new() -> Self {
+        use is_proc_translated::is_proc_translated;
+        if is_proc_translated() {
+            AppleEmulation::Rosetta
+        } else {
+            AppleEmulation::None
+        }
+    }
+}
+
+impl Default for AppleEmulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MacOSArch {
+    pub fn from(os_arch: Option<&str>) -> MacOSArch {
+        match os_arch {
+            Some("arm64") => MacOSArch::Arm64(AppleEmulation::new()),
+            other => MacOSArch::Other(other.map(|s| s.to_string())),
+        }
+    }
+}
+
+// The [Display] instance affects how [OS] is displayed to the app user
+impl Display for OS {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OS::MacOS {
+                nix_darwin,
+                arch: _,
+            } => {
+                if *nix_darwin {
+                    write!(f, "nix-darwin")
+                } else {
+                    write!(f, "macOS")
+                }
+            }
+            OS::NixOS => write!(f, "NixOS"),
+            OS::Other(os_type) => write!(f, "{}", os_type),
+        }
+    }
+}
+
+impl OS {
+    pub async fn detect() -> Self {
+        let os_info = tokio::task::spawn_blocking(os_info::get).await.unwrap();
+        let os_type = os_info.os_type();
+        let arch = MacOSArch::from(os_info.architecture());
+        async fn is_symlink(file_path: &str) -> std::io::Result<bool> {
+            let metadata = tokio::fs::symlink_metadata(file_path).await?;
+            Ok(metadata.file_type().is_symlink())
+        }
+        match os_type {
+            os_info::Type::Macos => {
+                // To detect that we are on NixDarwin, we check if /etc/nix/nix.conf
+                // is a symlink (which nix-darwin manages like NixOS does)
+                let nix_darwin = is_symlink("/etc/nix/nix.conf").await.unwrap_or(false);
+                OS::MacOS { nix_darwin, arch }
+            }
+            os_info::Type::NixOS => OS::NixOS,
+            _ => OS::Other(os_type),
+        }
+    }
+
+    /// Return the label for nix-darwin or NixOS system
+    pub fn nix_system_config_label(&self) -> Option<String> {
+        // TODO: This should return Markdown
+        match self {
+            OS::MacOS {
+                nix_darwin,
+                arch: _,
+            } if *nix_darwin => Some("nix-darwin configuration".to_string()),
+            OS::NixOS => Some("nixos configuration".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Return the label for where Nix is configured
+    pub fn nix_config_label(&self) -> String {
+        self.nix_system_config_label()
+            .unwrap_or("/etc/nix/nix.conf".to_string())
+    }
+
+    /// Return the label for where a setting with the given source should be
+    /// changed
+    ///
+    /// Settings from the system `nix.conf` are changed in the system
+    /// configuration (eg: NixOS), if any.
+    pub fn nix_setting_label(&self, source: Option<&ConfigSource>) -> String {
+        match source {
+            Some(ConfigSource::SystemFile(path)) => self
+                .nix_system_config_label()
+                .unwrap_or_else(|| path.display().to_string()),
+            Some(ConfigSource::UserFile(path)) => path.display().to_string(),
+            Some(ConfigSource::Env) => "the NIX_CONFIG environment variable".to_string(),
+            Some(ConfigSource::CommandLine) => "the options passed to nix".to_string(),
+            Some(ConfigSource::Default) | None => self.nix_config_label(),
+        }
+    }
+}
+
+/// Errors while trying to fetch [NixEnv]
+
+#[derive(thiserror::Error, Debug)]
+pub enum NixEnvError {
+    #[error("Cannot determine the name of the current user (uid {0}), and $USER is not set")]
+    UnknownUser(u32),
+
+    #[error("Failed to fetch groups: {0}")]
+    GroupsError(std::io::Error),
+
+    #[error("Unable to find root disk or /nix volume")]
+    NoDisk,
+}
+
+/// Convert bytes to a closest [ByteSize]
+///
+/// Useful for displaying disk space and memory which are typically in GBs / TBs
+
+fn to_bytesize(bytes: u64) -> ByteSize {
+    let kb = bytes / 1024;
+    let mb = kb / 1024;
+    let gb = mb / 1024;
+    if gb > 0 {
+        ByteSize::gib(gb)
+    } else if mb > 0 {
+        ByteSize::mib(mb)
+    } else if kb > 0 {
+        ByteSize::kib(kb)
+    } else {
+        ByteSize::b(bytes)
+    }
+}
+
+/// Test for [to_bytesize]
+
+#[test]
+fn test_to_bytesize() {
+    assert_eq!(to_bytesize(0), ByteSize::b(0));
+    assert_eq!(to_bytesize(1), ByteSize::b(1));
+    assert_eq!(to_bytesize(1023), ByteSize::b(1023));
+    assert_eq!(to_bytesize(1024), ByteSize::kib(1));
+    assert_eq!(to_bytesize(1024 * 1024), ByteSize::mib(1));
+    assert_eq!(to_bytesize(1024 * 1024 * 1024), ByteSize::gib(1));
+}
+
+#[test]
+fn test_nix_install_display() {
+    let install = NixInstall {
+        flavor: InstallFlavor::DeterminateSystems,
+        multi_user: true,
+        daemon_running: false,
+        store_dir: PathBuf::from("/nix/store"),
+        state_dir: PathBuf::from("/nix/var/nix"),
+    };
+    assert_eq!(
+        install.to_string(),
+        "Determinate Systems installer, multi-user (daemon not running)"
+    );
+    assert_eq!(
+        install.daemon_restart_command(&OS::NixOS),
+        Some("sudo systemctl restart nix-daemon")
+    );
+    let single_user = NixInstall {
+        multi_user: false,
+        ..install
+    };
+    assert_eq!(single_user.daemon_restart_command(&OS::NixOS), None);
+}
+
+#[test]
+fn test_dir_size() {
+    let dir = std::env::temp_dir().join(format!("nix-rs-dir-size-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a"), [0; 10]).unwrap();
+    std::fs::write(dir.join("sub").join("b"), [0; 5]).unwrap();
+    let size = dir_size(&dir, STORE_SIZE_TIMEOUT);
+    let timed_out = dir_size(&dir, Duration::ZERO);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(size, Some(15));
+    assert_eq!(timed_out, None);
+}
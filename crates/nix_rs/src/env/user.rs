@@ -0,0 +1,115 @@
+//! Resolve the current user and their groups from the process credentials
+//!
+//! Unlike `$USER` and the `groups` command, this works in minimal environments
+//! like containers and CI runners.
+use std::ffi::CStr;
+
+use super::NixEnvError;
+
+/// The name of the current user
+///
+/// Resolved from the real uid, falling back to `$USER` and then `$LOGNAME`
+/// when the uid has no passwd entry (eg: in a container run with `--user`).
+pub fn current_user() -> Result<String, NixEnvError> {
+    // SAFETY: getuid is always successful
+    let uid = unsafe { libc::getuid() };
+    // An empty name is as good as none, so fall through to the next candidate
+    let non_empty = |name: String| (!name.is_empty()).then_some(name);
+    user_name(uid)
+        .and_then(non_empty)
+        .or_else(|| std::env::var("USER").ok().and_then(non_empty))
+        .or_else(|| std::env::var("LOGNAME").ok().and_then(non_empty))
+        .ok_or(NixEnvError::UnknownUser(uid))
+}
+
+/// The names of the groups of the current user (primary and supplementary)
+///
+/// Groups without a name are represented by their gid, as `groups` does.
+pub fn current_user_groups() -> Result<Vec<String>, NixEnvError> {
+    // SAFETY: getgroups with a size of 0 only returns the number of groups
+    let n = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if n < 0 {
+        return Err(NixEnvError::GroupsError(std::io::Error::last_os_error()));
+    }
+    let mut gids: Vec<libc::gid_t> = vec![0; n as usize];
+    // SAFETY: `gids` has room for `n` groups
+    let n = unsafe { libc::getgroups(n, gids.as_mut_ptr()) };
+    if n < 0 {
+        return Err(NixEnvError::GroupsError(std::io::Error::last_os_error()));
+    }
+    gids.truncate(n as usize);
+    // SAFETY: getegid is always successful
+    let primary = unsafe { libc::getegid() };
+    if !gids.contains(&primary) {
+        gids.insert(0, primary);
+    }
+    Ok(gids
+        .into_iter()
+        .map(|gid| group_name(gid).unwrap_or_else(|| gid.to_string()))
+        .collect())
+}
+
+/// Look up the name of the user with the given uid
+fn user_name(uid: libc::uid_t) -> Option<String> {
+    with_buffer(|buf| {
+        // SAFETY: all-zero is a valid `passwd` (null pointers and zero ids)
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        // SAFETY: the pointers are valid for the duration of the call, and
+        // `buf.len()` is the size of `buf`
+        let rc =
+            unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+        if rc != 0 || result.is_null() {
+            return Err(rc);
+        }
+        // SAFETY: on success, `pw_name` points to a nul-terminated string in `buf`
+        Ok(unsafe { CStr::from_ptr(pwd.pw_name) }
+            .to_string_lossy()
+            .into_owned())
+    })
+}
+
+/// Look up the name of the group with the given gid
+pub(super) fn group_name(gid: libc::gid_t) -> Option<String> {
+    with_buffer(|buf| {
+        // SAFETY: all-zero is a valid `group` (null pointers and zero ids)
+        let mut grp: libc::group = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        // SAFETY: the pointers are valid for the duration of the call, and
+        // `buf.len()` is the size of `buf`
+        let rc =
+            unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+        if rc != 0 || result.is_null() {
+            return Err(rc);
+        }
+        // SAFETY: on success, `gr_name` points to a nul-terminated string in `buf`
+        Ok(unsafe { CStr::from_ptr(grp.gr_name) }
+            .to_string_lossy()
+            .into_owned())
+    })
+}
+
+/// Call a `get*_r` style lookup, growing its buffer while it is too small
+fn with_buffer<T>(f: impl Fn(&mut [libc::c_char]) -> Result<T, libc::c_int>) -> Option<T> {
+    let mut buf = vec![0; 1024];
+    loop {
+        match f(&mut buf) {
+            Ok(v) => return Some(v),
+            Err(libc::ERANGE) if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            Err(_) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_user() {
+        // Root always has a passwd entry
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert!(!current_user().unwrap().is_empty());
+        assert!(!current_user_groups().unwrap().is_empty());
+    }
+}
@@ -9,9 +9,22 @@ pub async fn nix_eval_attr<T>(cmd: &NixCmd, url: &FlakeUrl) -> Result<Option<T>,
 where
     T: Default + serde::de::DeserializeOwned,
 {
-    let result = cmd
-        .run_with_args_expecting_json(&["eval", &url.0, "--json"])
-        .await;
+    nix_eval_attr_with_args(cmd, url, &[]).await
+}
+
+/// Like [nix_eval_attr], but passing extra arguments to `nix eval` (eg:
+/// `--apply` or `--override-input`)
+pub async fn nix_eval_attr_with_args<T>(
+    cmd: &NixCmd,
+    url: &FlakeUrl,
+    extra_args: &[&str],
+) -> Result<Option<T>, NixCmdError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let url = url.to_string();
+    let args = [&["eval", url.as_str(), "--json"], extra_args].concat();
+    let result = cmd.run_with_args_expecting_json(&args).await;
     match result {
         Ok(v) => Ok(Some(v)),
         Err(err) if error_is_missing_attribute(&err) => {
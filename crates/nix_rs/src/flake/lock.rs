@@ -0,0 +1,479 @@
+//! Rust types for the `flake.lock` file
+//!
+//! Lock files are parsed without invoking Nix, so the input graph of a flake
+//! can be inspected offline.
+//!
+//! See <https://nixos.org/manual/nix/stable/command-ref/new-cli/nix3-flake.html#lock-files>
+use std::{collections::BTreeMap, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use super::url::{
+    kind::{FlakeUrlKind, Forge},
+    FlakeUrl,
+};
+
+/// The contents of a `flake.lock` file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlakeLock {
+    /// All nodes in the lock graph, indexed by their name
+    pub nodes: BTreeMap<String, Node>,
+    /// Name of the node representing the flake itself
+    pub root: String,
+    /// Lock file format version
+    pub version: u32,
+}
+
+/// The only `flake.lock` format version supported by [FlakeLock]
+pub const LOCK_FILE_VERSION: u32 = 7;
+
+/// Errors when reading a [FlakeLock]
+#[derive(thiserror::Error, Debug)]
+pub enum FlakeLockError {
+    #[error("Unable to read lock file: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("Unable to parse lock file: {0}")]
+    JSONError(#[from] serde_json::Error),
+
+    #[error("Unsupported lock file version {0} (expected {LOCK_FILE_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+impl FromStr for FlakeLock {
+    type Err = FlakeLockError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lock: FlakeLock = serde_json::from_str(s)?;
+        if lock.version != LOCK_FILE_VERSION {
+            return Err(FlakeLockError::UnsupportedVersion(lock.version));
+        }
+        Ok(lock)
+    }
+}
+
+impl FlakeLock {
+    /// Read the given `flake.lock` file
+    pub fn from_file(path: &Path) -> Result<Self, FlakeLockError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Read the `flake.lock` file of the flake in the given directory
+    pub fn from_flake_dir(dir: &Path) -> Result<Self, FlakeLockError> {
+        Self::from_file(&dir.join("flake.lock"))
+    }
+
+    /// Get the node by the given name
+    pub fn node(&self, name: &str) -> Option<&Node> {
+        self.nodes.get(name)
+    }
+
+    /// The inputs of the flake itself
+    pub fn root_inputs(&self) -> impl Iterator<Item = (&String, &NodeInput)> {
+        self.node(&self.root)
+            .into_iter()
+            .flat_map(|node| node.inputs.iter())
+    }
+
+    /// The locked [FlakeUrl] of the given input
+    ///
+    /// Returns `None` for inputs that follow another input.
+    pub fn input_url(&self, input: &NodeInput) -> Option<FlakeUrl> {
+        match input {
+            NodeInput::Node(name) => self.node(name)?.locked.as_ref().map(|l| l.to_flake_url()),
+            NodeInput::Follows(_) => None,
+        }
+    }
+
+    /// Resolve the given input to the name of the node it refers to,
+    /// following any `follows` indirections.
+    pub fn resolve_input<'a>(&'a self, input: &'a NodeInput) -> Option<&'a str> {
+        // A valid lock file cannot have a chain of `follows` longer than the
+        // number of nodes; bail out on cycles.
+        self.resolve_input_bounded(input, self.nodes.len())
+    }
+
+    /// Resolve a path of input names, starting from the root (eg:
+    /// `["flake-parts", "nixpkgs-lib"]`), to the name of the node it refers to.
+    pub fn resolve_path<S: AsRef<str>>(&self, path: &[S]) -> Option<&str> {
+        self.resolve_path_bounded(path, self.nodes.len())
+    }
+
+    fn resolve_input_bounded<'a>(&'a self, input: &'a NodeInput, depth: usize) -> Option<&'a str> {
+        match input {
+            NodeInput::Node(name) => Some(name.as_str()),
+            NodeInput::Follows(path) => self.resolve_path_bounded(path, depth.checked_sub(1)?),
+        }
+    }
+
+    fn resolve_path_bounded<S: AsRef<str>>(&self, path: &[S], depth: usize) -> Option<&str> {
+        let mut current = self.root.as_str();
+        for name in path {
+            let input = self.node(current)?.inputs.get(name.as_ref())?;
+            current = self.resolve_input_bounded(input, depth)?;
+        }
+        Some(current)
+    }
+
+    /// The inputs of the given node, resolved to the names of the nodes they
+    /// refer to. Inputs that cannot be resolved are left out.
+    pub fn resolved_inputs(&self, node: &str) -> BTreeMap<&str, &str> {
+        self.node(node)
+            .into_iter()
+            .flat_map(|node| node.inputs.iter())
+            .filter_map(|(name, input)| Some((name.as_str(), self.resolve_input(input)?)))
+            .collect()
+    }
+
+    /// Names of all nodes reachable from the root, excluding the root itself
+    pub fn reachable_nodes(&self) -> Vec<&str> {
+        let mut seen = std::collections::BTreeSet::new();
+        let mut stack = vec![self.root.as_str()];
+        while let Some(name) = stack.pop() {
+            for input in self.resolved_inputs(name).into_values() {
+                if seen.insert(input) {
+                    stack.push(input);
+                }
+            }
+        }
+        seen.remove(self.root.as_str());
+        seen.into_iter().collect()
+    }
+
+    /// Groups of (reachable) nodes that pin the same source at different
+    /// versions, such as multiple revisions of nixpkgs.
+    pub fn duplicates(&self) -> Vec<Vec<&str>> {
+        let mut groups: Vec<Vec<(&str, &LockedRef)>> = vec![];
+        for name in self.reachable_nodes() {
+            let Some(locked) = self.node(name).and_then(|n| n.locked.as_ref()) else {
+                continue;
+            };
+            match groups.iter_mut().find(|g| g[0].1.same_source(locked)) {
+                Some(group) => group.push((name, locked)),
+                None => groups.push(vec![(name, locked)]),
+            }
+        }
+        groups
+            .into_iter()
+            .filter(|g| g.iter().any(|(_, l)| l.nar_hash != g[0].1.nar_hash))
+            .map(|g| g.into_iter().map(|(name, _)| name).collect())
+            .collect()
+    }
+
+    /// Number of distinct revisions (by `narHash`) pinned by the given nodes,
+    /// such as a group of [FlakeLock::duplicates]
+    pub fn revisions(&self, nodes: &[&str]) -> usize {
+        nodes
+            .iter()
+            .filter_map(|name| self.node(name)?.locked.as_ref())
+            .map(|locked| &locked.nar_hash)
+            .collect::<std::collections::BTreeSet<_>>()
+            .len()
+    }
+}
+
+/// A node in the lock graph
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Node {
+    /// Inputs of this node
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, NodeInput>,
+    /// The locked reference (absent for the root node)
+    pub locked: Option<LockedRef>,
+    /// The reference as originally specified in `flake.nix`
+    pub original: Option<LockedRef>,
+    /// Whether the input is a flake (`false` for `flake = false` inputs)
+    pub flake: Option<bool>,
+}
+
+/// An input of a [Node]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NodeInput {
+    /// Name of the node in [FlakeLock::nodes]
+    Node(String),
+    /// Path of input names, starting from the root, as set by `inputs.<name>.follows`
+    Follows(Vec<String>),
+}
+
+/// A flake reference in attribute set form, as found in lock files
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedRef {
+    /// Fetcher type (eg: `github`, `git`, `path`, `tarball`)
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub owner: Option<String>,
+    pub repo: Option<String>,
+    pub host: Option<String>,
+    pub url: Option<String>,
+    pub path: Option<String>,
+    /// Flake registry id (for `indirect` references)
+    pub id: Option<String>,
+    #[serde(rename = "ref")]
+    pub ref_: Option<String>,
+    pub rev: Option<String>,
+    pub dir: Option<String>,
+    pub nar_hash: Option<String>,
+    pub last_modified: Option<u64>,
+    /// Other attributes not covered by the fields above
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+impl LockedRef {
+    /// Whether both refer to the same source (eg: the same GitHub repository),
+    /// regardless of the revision.
+    pub fn same_source(&self, other: &LockedRef) -> bool {
+        let lower = |v: &Option<String>| v.as_ref().map(|s| s.to_lowercase());
+        self.type_ == other.type_
+            // GitHub and GitLab owner and repo names are case-insensitive
+            && lower(&self.owner) == lower(&other.owner)
+            && lower(&self.repo) == lower(&other.repo)
+            && self.host == other.host
+            && self.url == other.url
+            && self.path == other.path
+            && self.dir == other.dir
+    }
+
+    /// The [FlakeUrl] of the source, without any revision or branch
+    pub fn source_url(&self) -> FlakeUrl {
+        LockedRef {
+            ref_: None,
+            rev: None,
+            ..self.clone()
+        }
+        .to_flake_url()
+    }
+
+    /// Convert to the URL-like syntax of a flake reference
+    pub fn to_flake_url(&self) -> FlakeUrl {
+        let string = |v: &Option<String>| v.clone().unwrap_or_default();
+        let mut params = vec![];
+        let kind = match self.type_.as_str() {
+            "github" | "gitlab" | "sourcehut" => {
+                params.push(("host", &self.host));
+                FlakeUrlKind::Forge {
+                    forge: match self.type_.as_str() {
+                        "github" => Forge::GitHub,
+                        "gitlab" => Forge::GitLab,
+                        _ => Forge::SourceHut,
+                    },
+                    owner: string(&self.owner),
+                    repo: string(&self.repo),
+                    ref_or_rev: self.rev.clone().or(self.ref_.clone()),
+                }
+            }
+            "git" | "hg" => {
+                params.push(("ref", &self.ref_));
+                params.push(("rev", &self.rev));
+                let url = string(&self.url);
+                if self.type_ == "git" {
+                    FlakeUrlKind::Git {
+                        url,
+                        explicit: true,
+                    }
+                } else {
+                    FlakeUrlKind::Mercurial { url }
+                }
+            }
+            "path" => FlakeUrlKind::Path {
+                path: string(&self.path),
+                explicit: true,
+            },
+            "indirect" => FlakeUrlKind::Indirect {
+                id: string(&self.id),
+                ref_or_rev: self.ref_.clone().or(self.rev.clone()),
+                rev: self.ref_.as_ref().and(self.rev.clone()),
+                explicit: true,
+            },
+            "tarball" => FlakeUrlKind::Tarball {
+                url: string(&self.url),
+                explicit: true,
+            },
+            // `file`, and any fetcher type unknown to us, is fetched by URL
+            _ => FlakeUrlKind::File {
+                url: string(&self.url),
+                explicit: true,
+            },
+        };
+        params.push(("dir", &self.dir));
+        FlakeUrl {
+            kind,
+            params: params
+                .into_iter()
+                .filter_map(|(k, v)| Some((k.to_string(), v.clone()?)))
+                .collect(),
+            attr: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAKE_LOCK: &str = r#"{
+  "nodes": {
+    "flake-parts": {
+      "inputs": {
+        "nixpkgs-lib": ["nixpkgs"]
+      },
+      "locked": {
+        "lastModified": 1719994518,
+        "narHash": "sha256-pQMhCCHyQGRzdfAkdJ4cIWiw+JNuWsTX7f0ZYSyz0VY=",
+        "owner": "hercules-ci",
+        "repo": "flake-parts",
+        "rev": "9227223f6d922fee3c7b190b2cc238a99527bbb7",
+        "type": "github"
+      },
+      "original": {
+        "owner": "hercules-ci",
+        "repo": "flake-parts",
+        "type": "github"
+      }
+    },
+    "nixpkgs": {
+      "locked": {
+        "lastModified": 1720542800,
+        "narHash": "sha256-ZgnNHuKV6h2+fQ5LuqnUaqZey1Lqqt5dTUAiAnqH0QQ=",
+        "owner": "nixos",
+        "repo": "nixpkgs",
+        "rev": "feb2849fdeb70028c70d73b848214b00d324a497",
+        "type": "github"
+      },
+      "original": {
+        "owner": "nixos",
+        "ref": "nixpkgs-unstable",
+        "repo": "nixpkgs",
+        "type": "github"
+      }
+    },
+    "root": {
+      "inputs": {
+        "flake-parts": "flake-parts",
+        "nixpkgs": "nixpkgs"
+      }
+    }
+  },
+  "root": "root",
+  "version": 7
+}"#;
+
+    #[test]
+    fn test_parse_flake_lock() {
+        let lock: FlakeLock = serde_json::from_str(FLAKE_LOCK).unwrap();
+        assert_eq!(lock.version, 7);
+        assert_eq!(lock.nodes.len(), 3);
+        assert_eq!(
+            lock.root_inputs()
+                .map(|(k, _)| k.as_str())
+                .collect::<Vec<_>>(),
+            vec!["flake-parts", "nixpkgs"]
+        );
+        assert_eq!(
+            lock.node("flake-parts").unwrap().inputs["nixpkgs-lib"],
+            NodeInput::Follows(vec!["nixpkgs".to_string()])
+        );
+        assert_eq!(
+            lock.input_url(&NodeInput::Node("nixpkgs".to_string())),
+            Some(
+                "github:nixos/nixpkgs/feb2849fdeb70028c70d73b848214b00d324a497"
+                    .parse()
+                    .unwrap()
+            )
+        );
+        // Round-trips through JSON
+        let json = serde_json::to_string(&lock).unwrap();
+        assert_eq!(serde_json::from_str::<FlakeLock>(&json).unwrap(), lock);
+    }
+
+    #[test]
+    fn test_follows() {
+        let lock: FlakeLock = FLAKE_LOCK.parse().unwrap();
+        assert_eq!(
+            lock.resolve_path(&["flake-parts", "nixpkgs-lib"]),
+            Some("nixpkgs")
+        );
+        assert_eq!(lock.resolve_path(&["flake-parts", "missing"]), None);
+        assert_eq!(
+            lock.resolved_inputs("flake-parts"),
+            BTreeMap::from([("nixpkgs-lib", "nixpkgs")])
+        );
+        assert_eq!(lock.reachable_nodes(), vec!["flake-parts", "nixpkgs"]);
+        assert!(lock.duplicates().is_empty());
+    }
+
+    #[test]
+    fn test_follows_cycle() {
+        let lock: FlakeLock = r#"{
+          "nodes": {
+            "a": { "inputs": { "b": ["a", "b"] }, "locked": { "type": "path", "path": "/a" } },
+            "root": { "inputs": { "a": "a" } }
+          },
+          "root": "root",
+          "version": 7
+        }"#
+        .parse()
+        .unwrap();
+        assert_eq!(lock.resolve_path(&["a", "b"]), None);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let lock: FlakeLock = r#"{
+          "nodes": {
+            "nixpkgs": { "locked": { "type": "github", "owner": "nixos", "repo": "nixpkgs", "rev": "a", "narHash": "sha256-a" } },
+            "nixpkgs_2": { "locked": { "type": "github", "owner": "NixOS", "repo": "nixpkgs", "rev": "b", "narHash": "sha256-b" } },
+            "nixpkgs_3": { "locked": { "type": "github", "owner": "nixos", "repo": "nixpkgs", "rev": "a", "narHash": "sha256-a" } },
+            "other": { "inputs": { "nixpkgs": "nixpkgs_2" }, "locked": { "type": "path", "path": "/other" } },
+            "another": { "inputs": { "nixpkgs": "nixpkgs_3" }, "locked": { "type": "path", "path": "/another" } },
+            "root": { "inputs": { "nixpkgs": "nixpkgs", "other": "other", "another": "another" } }
+          },
+          "root": "root",
+          "version": 7
+        }"#
+        .parse()
+        .unwrap();
+        let duplicates = lock.duplicates();
+        assert_eq!(duplicates, vec![vec!["nixpkgs", "nixpkgs_2", "nixpkgs_3"]]);
+        // `nixpkgs` and `nixpkgs_3` pin the same revision
+        assert_eq!(lock.revisions(&duplicates[0]), 2);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let err = r#"{"nodes": {"root": {}}, "root": "root", "version": 6}"#
+            .parse::<FlakeLock>()
+            .unwrap_err();
+        assert!(matches!(err, FlakeLockError::UnsupportedVersion(6)));
+    }
+
+    #[test]
+    fn test_locked_ref_to_flake_url() {
+        let git: LockedRef = serde_json::from_str(
+            r#"{"type": "git", "url": "https://example.org/repo", "ref": "main", "rev": "abc", "dir": "sub"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            git.to_flake_url(),
+            "git+https://example.org/repo?ref=main&rev=abc&dir=sub"
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            git.source_url(),
+            "git+https://example.org/repo?dir=sub".parse().unwrap()
+        );
+        let path: LockedRef =
+            serde_json::from_str(r#"{"type": "path", "path": "/foo", "narHash": "sha256-x"}"#)
+                .unwrap();
+        assert_eq!(path.to_flake_url(), "path:/foo".parse().unwrap());
+        let indirect: LockedRef =
+            serde_json::from_str(r#"{"type": "indirect", "id": "nixpkgs"}"#).unwrap();
+        assert_eq!(indirect.to_flake_url(), "flake:nixpkgs".parse().unwrap());
+    }
+}
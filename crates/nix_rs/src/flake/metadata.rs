@@ -0,0 +1,142 @@
+//! Rust module for `nix flake metadata`
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use super::{
+    lock::{FlakeLock, LockedRef},
+    url::FlakeUrl,
+};
+use crate::command::{NixCmd, NixCmdError};
+
+/// Metadata of a flake, as returned by `nix flake metadata --json`
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlakeMetadata {
+    /// The `description` attribute of `flake.nix`
+    pub description: Option<String>,
+    /// The flake URL as specified by the user
+    pub original_url: FlakeUrl,
+    /// The flake URL after resolving it through the flake registry
+    pub resolved_url: FlakeUrl,
+    /// The locked flake URL
+    pub url: FlakeUrl,
+    /// Git revision of the flake (absent if the working tree is dirty)
+    pub revision: Option<String>,
+    /// Git revision, suffixed with `-dirty`, of a dirty working tree
+    pub dirty_revision: Option<String>,
+    /// Time of the last commit, in seconds since the Unix epoch
+    pub last_modified: Option<u64>,
+    /// Store path of the flake source
+    pub path: PathBuf,
+    /// The locked reference, in attribute set form
+    pub locked: LockedRef,
+    /// The lock file of the flake
+    pub locks: FlakeLock,
+}
+
+impl FlakeMetadata {
+    /// Run `nix flake metadata` on the given flake url
+    pub async fn from_nix(nix_cmd: &NixCmd, flake_url: &FlakeUrl) -> Result<Self, NixCmdError> {
+        nix_cmd
+            .run_with_args_expecting_json(&["flake", "metadata", "--json", &flake_url.to_string()])
+            .await
+    }
+
+    /// The revision of the flake, dirty or not
+    pub fn any_revision(&self) -> Option<&str> {
+        self.revision.as_deref().or(self.dirty_revision.as_deref())
+    }
+
+    /// [Self::last_modified] as a [SystemTime]
+    pub fn last_modified_time(&self) -> Option<SystemTime> {
+        self.last_modified
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{
+  "description": "Example flake",
+  "lastModified": 1720542800,
+  "locked": {
+    "lastModified": 1720542800,
+    "narHash": "sha256-ZgnNHuKV6h2+fQ5LuqnUaqZey1Lqqt5dTUAiAnqH0QQ=",
+    "owner": "srid",
+    "repo": "example",
+    "rev": "feb2849fdeb70028c70d73b848214b00d324a497",
+    "type": "github"
+  },
+  "locks": {
+    "nodes": {
+      "nixpkgs": {
+        "locked": {
+          "lastModified": 1720542800,
+          "narHash": "sha256-ZgnNHuKV6h2+fQ5LuqnUaqZey1Lqqt5dTUAiAnqH0QQ=",
+          "owner": "nixos",
+          "repo": "nixpkgs",
+          "rev": "feb2849fdeb70028c70d73b848214b00d324a497",
+          "type": "github"
+        },
+        "original": {
+          "owner": "nixos",
+          "ref": "nixpkgs-unstable",
+          "repo": "nixpkgs",
+          "type": "github"
+        }
+      },
+      "root": {
+        "inputs": {
+          "nixpkgs": "nixpkgs"
+        }
+      }
+    },
+    "root": "root",
+    "version": 7
+  },
+  "original": {
+    "owner": "srid",
+    "repo": "example",
+    "type": "github"
+  },
+  "originalUrl": "github:srid/example",
+  "path": "/nix/store/3q7kb8fqm2m0rq4wbvvn0ws6z7wy6crb-source",
+  "resolved": {
+    "owner": "srid",
+    "repo": "example",
+    "type": "github"
+  },
+  "resolvedUrl": "github:srid/example",
+  "revision": "feb2849fdeb70028c70d73b848214b00d324a497",
+  "url": "github:srid/example/feb2849fdeb70028c70d73b848214b00d324a497"
+}"#;
+
+    #[test]
+    fn test_parse_flake_metadata() {
+        let metadata: FlakeMetadata = serde_json::from_str(METADATA).unwrap();
+        assert_eq!(metadata.description.as_deref(), Some("Example flake"));
+        assert_eq!(
+            metadata.url,
+            "github:srid/example/feb2849fdeb70028c70d73b848214b00d324a497"
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            metadata.any_revision(),
+            Some("feb2849fdeb70028c70d73b848214b00d324a497")
+        );
+        assert_eq!(metadata.locks.root_inputs().count(), 1);
+        assert_eq!(
+            metadata.last_modified_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(1720542800))
+        );
+    }
+}
@@ -1,6 +1,8 @@
 //! Rust module for Nix flakes
 
 pub mod eval;
+pub mod lock;
+pub mod metadata;
 pub mod outputs;
 pub mod schema;
 pub mod system;
@@ -10,7 +12,10 @@ use serde::{Deserialize, Serialize};
 
 use tracing::instrument;
 
-use self::{outputs::FlakeOutputs, schema::FlakeSchema, system::System, url::FlakeUrl};
+use self::{
+    metadata::FlakeMetadata, outputs::FlakeOutputs, schema::FlakeSchema, system::System,
+    url::FlakeUrl,
+};
 
 use crate::{
     command::{NixCmd, NixCmdError},
@@ -26,7 +31,8 @@ pub struct Flake {
     pub output: FlakeOutputs,
     /// Flake output schema (typed version of [FlakeOutputs])
     pub schema: FlakeSchema,
-    // TODO: Add `nix flake metadata` info.
+    /// `nix flake metadata` output
+    pub metadata: FlakeMetadata,
 }
 
 impl Flake {
@@ -38,12 +44,16 @@ impl Flake {
         nix_config: &NixConfig,
         url: FlakeUrl,
     ) -> Result<Flake, NixCmdError> {
-        let output = FlakeOutputs::from_nix(nix_cmd, &url).await?;
+        let (output, metadata) = tokio::try_join!(
+            FlakeOutputs::from_nix(nix_cmd, &url),
+            FlakeMetadata::from_nix(nix_cmd, &url)
+        )?;
         let schema = FlakeSchema::from(&output, &nix_config.system.value);
         Ok(Flake {
             url,
             output: output.clone(),
             schema,
+            metadata,
         })
     }
 }
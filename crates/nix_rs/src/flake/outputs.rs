@@ -51,6 +51,12 @@ impl FlakeOutputs {
         }
     }
 
+    /// Lookup the given path, returning a reference to the value
+    pub fn get(&self, path: &[&str]) -> Option<&Self> {
+        path.iter()
+            .try_fold(self, |curr, part| curr.as_attrset()?.get(*part))
+    }
+
     /// Lookup the given path, returning the value, while removing it from the tree.
     ///
     /// # Example
@@ -149,48 +155,127 @@ pub struct Skipped {
 /// These types can differ based on [DEFAULT_FLAKE_SCHEMAS].
 /// The types here are based on <https://github.com/DeterminateSystems/flake-schemas>
 /// For example, see [NixosModule type](https://github.com/DeterminateSystems/flake-schemas/blob/0a5c42297d870156d9c57d8f99e476b738dcd982/flake.nix#L268)
+///
+/// Types defined by other flake schemas are kept as is in [Type::Other].
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum Type {
-    #[serde(rename = "NixOS module")]
     NixosModule,
-    #[serde(rename = "NixOS configuration")]
     NixosConfiguration,
-    #[serde(rename = "nix-darwin configuration")]
+    DarwinModule,
     DarwinConfiguration,
-    #[serde(rename = "package")]
+    HomeModule,
+    HomeConfiguration,
     Package,
-    #[serde(rename = "development environment")]
     DevShell,
-    #[serde(rename = "CI test")]
     Check,
-    #[serde(rename = "app")]
     App,
-    #[serde(rename = "template")]
     Template,
-    #[serde(other)]
+    Overlay,
+    Formatter,
+    DockerImage,
+    Lib,
+    Schema,
+    FlakeModule,
+    /// A type (the `what` string) not known to us
+    Other(String),
+    /// The type is not known
     Unknown,
 }
 
 impl Type {
+    /// The `what` string of this type, as given by the flake schema
+    pub fn what(&self) -> &str {
+        match self {
+            Self::NixosModule => "NixOS module",
+            Self::NixosConfiguration => "NixOS configuration",
+            Self::DarwinModule => "nix-darwin module",
+            Self::DarwinConfiguration => "nix-darwin configuration",
+            Self::HomeModule => "Home Manager module",
+            Self::HomeConfiguration => "Home Manager configuration",
+            Self::Package => "package",
+            Self::DevShell => "development environment",
+            Self::Check => "CI test",
+            Self::App => "app",
+            Self::Template => "template",
+            Self::Overlay => "Nixpkgs overlay",
+            Self::Formatter => "formatter",
+            Self::DockerImage => "Docker image",
+            Self::Lib => "library",
+            Self::Schema => "flake schema",
+            Self::FlakeModule => "flake-parts module",
+            Self::Other(what) => what,
+            Self::Unknown => "unknown",
+        }
+    }
+
     /// Get the icon for this type
     pub fn to_icon(&self) -> &'static str {
         match self {
             Self::NixosModule => "❄️",
             Self::NixosConfiguration => "🔧",
+            Self::DarwinModule => "🍏",
             Self::DarwinConfiguration => "🍎",
+            Self::HomeModule => "🏡",
+            Self::HomeConfiguration => "🏠",
             Self::Package => "📦",
             Self::DevShell => "🐚",
             Self::Check => "🧪",
             Self::App => "📱",
             Self::Template => "🏗️",
+            Self::Overlay => "🎨",
+            Self::Formatter => "🧹",
+            Self::DockerImage => "🐳",
+            Self::Lib => "📚",
+            Self::Schema => "📜",
+            Self::FlakeModule => "🧩",
+            Self::Other(_) => "🔹",
             Self::Unknown => "❓",
         }
     }
 }
 
+impl From<String> for Type {
+    fn from(what: String) -> Self {
+        let known = [
+            Self::NixosModule,
+            Self::NixosConfiguration,
+            Self::DarwinModule,
+            Self::DarwinConfiguration,
+            Self::HomeModule,
+            Self::HomeConfiguration,
+            Self::Package,
+            Self::DevShell,
+            Self::Check,
+            Self::App,
+            Self::Template,
+            Self::Overlay,
+            Self::Formatter,
+            Self::DockerImage,
+            Self::Lib,
+            Self::Schema,
+            Self::FlakeModule,
+            Self::Unknown,
+        ];
+        known
+            .into_iter()
+            .find(|t| t.what() == what)
+            .unwrap_or(Self::Other(what))
+    }
+}
+
+impl From<Type> for String {
+    fn from(type_: Type) -> Self {
+        type_.what().to_string()
+    }
+}
+
 impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{:?}", self))
+        match self {
+            Self::Other(what) => f.write_str(what),
+            _ => f.write_str(&format!("{:?}", self)),
+        }
     }
 }
 
@@ -199,7 +284,7 @@ impl Display for Type {
 /// This separation exists to workaround <https://github.com/DioxusLabs/dioxus-std/issues/20>
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(untagged)]
-enum FlakeOutputsUntagged {
+pub(crate) enum FlakeOutputsUntagged {
     ULeaf(Leaf),
     UAttrset(BTreeMap<String, FlakeOutputsUntagged>),
 }
@@ -214,23 +299,26 @@ impl FlakeOutputsUntagged {
         let mut nix_flake_schemas_cmd = nix_cmd.clone();
         nix_flake_schemas_cmd.command = Some(env!("NIX_FLAKE_SCHEMAS_BIN").to_string());
 
+        let flake_url = flake_url.to_string();
+        // The flake-schemas `nix` is pinned at build time, and always supports `--legacy`
+        let args = [
+            "flake",
+            "show",
+            "--legacy", // for showing nixpkgs legacyPackages
+            "--allow-import-from-derivation",
+            "--json",
+            "--default-flake-schemas",
+            env!("DEFAULT_FLAKE_SCHEMAS"),
+            &flake_url,
+        ];
         let v = nix_flake_schemas_cmd
-            .run_with_args_expecting_json(&[
-                "flake",
-                "show",
-                "--legacy", // for showing nixpkgs legacyPackages
-                "--allow-import-from-derivation",
-                "--json",
-                "--default-flake-schemas",
-                env!("DEFAULT_FLAKE_SCHEMAS"),
-                &flake_url.to_string(),
-            ])
+            .run_with_args_expecting_json(&args)
             .await?;
         Ok(v)
     }
 
     /// Convert to [FlakeOutputs]
-    fn into_flake_outputs(self) -> FlakeOutputs {
+    pub(crate) fn into_flake_outputs(self) -> FlakeOutputs {
         match self {
             Self::ULeaf(v) => FlakeOutputs::Leaf(v),
             Self::UAttrset(v) => FlakeOutputs::Attrset(
@@ -241,3 +329,29 @@ impl FlakeOutputsUntagged {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_serde() {
+        let json = r#"["package", "Home Manager configuration", "my custom thing"]"#;
+        let types: Vec<Type> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            types,
+            vec![
+                Type::Package,
+                Type::HomeConfiguration,
+                Type::Other("my custom thing".to_string())
+            ]
+        );
+        assert_eq!(types[2].to_string(), "my custom thing");
+        // Lossless
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&serde_json::to_string(&types).unwrap())
+                .unwrap(),
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+    }
+}
@@ -1,7 +1,7 @@
 //! High-level schema of a flake
 //!
 //! TODO: Use <https://github.com/DeterminateSystems/flake-schemas>
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +26,9 @@ pub struct FlakeSchema {
     pub darwin_configurations: BTreeMap<String, Leaf>,
     pub home_configurations: BTreeMap<String, Leaf>,
     pub nixos_modules: BTreeMap<String, Leaf>,
+    pub darwin_modules: BTreeMap<String, Leaf>,
+    pub home_modules: BTreeMap<String, Leaf>,
+    pub flake_modules: BTreeMap<String, Leaf>,
     pub docker_images: BTreeMap<String, Leaf>,
     pub overlays: BTreeMap<String, Leaf>,
     pub templates: BTreeMap<String, Leaf>,
@@ -34,7 +37,40 @@ pub struct FlakeSchema {
     pub other: Option<BTreeMap<String, FlakeOutputs>>,
 }
 
+/// Output types that are defined per system (eg: `packages.<system>.<name>`)
+pub const PER_SYSTEM_OUTPUTS: &[&str] = &[
+    "packages",
+    "legacyPackages",
+    "devShells",
+    "checks",
+    "apps",
+    "formatter",
+];
+
 impl FlakeSchema {
+    /// Builds the [PerSystemSchema] for every system the flake supports
+    ///
+    /// Outputs of systems other than the current one are typically not
+    /// evaluated by `nix flake show`, in which case their [Leaf] is
+    /// [Leaf::Filtered] (with no metadata).
+    pub fn all_systems(output: &FlakeOutputs) -> MultiSystemSchema {
+        let systems = PER_SYSTEM_OUTPUTS
+            .iter()
+            .filter_map(|k| output.get(&[k, "output", "children"])?.as_attrset())
+            .flat_map(|children| children.keys())
+            .map(|system| System::from(system.as_str()))
+            .collect::<BTreeSet<_>>();
+        MultiSystemSchema {
+            systems: systems
+                .into_iter()
+                .map(|system| {
+                    let schema = FlakeSchema::from(output, &system);
+                    (system, PerSystemSchema::from(schema))
+                })
+                .collect(),
+        }
+    }
+
     /// Builds the [FlakeSchema] for the given system
     ///
     /// Other system outputs are eliminated, but non-per-system outputs are kept
@@ -88,6 +124,9 @@ impl FlakeSchema {
             ),
             home_configurations: pop_tree(output, &["homeConfigurations", "output", "children"]),
             nixos_modules: pop_tree(output, &["nixosModules", "output", "children"]),
+            darwin_modules: pop_tree(output, &["darwinModules", "output", "children"]),
+            home_modules: pop_tree(output, &["homeModules", "output", "children"]),
+            flake_modules: pop_tree(output, &["flakeModules", "output", "children"]),
             docker_images: pop_tree(output, &["dockerImages", "output", "children"]),
             overlays: pop_tree(output, &["overlays", "output", "children"]),
             templates: pop_tree(output, &["templates", "output", "children"]),
@@ -96,3 +135,134 @@ impl FlakeSchema {
         }
     }
 }
+
+/// The per-system outputs of a [FlakeSchema]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PerSystemSchema {
+    pub packages: BTreeMap<String, Leaf>,
+    pub legacy_packages: BTreeMap<String, Leaf>,
+    pub devshells: BTreeMap<String, Leaf>,
+    pub checks: BTreeMap<String, Leaf>,
+    pub apps: BTreeMap<String, Leaf>,
+    pub formatter: Option<Leaf>,
+}
+
+impl From<FlakeSchema> for PerSystemSchema {
+    fn from(schema: FlakeSchema) -> Self {
+        PerSystemSchema {
+            packages: schema.packages,
+            legacy_packages: schema.legacy_packages,
+            devshells: schema.devshells,
+            checks: schema.checks,
+            apps: schema.apps,
+            formatter: schema.formatter,
+        }
+    }
+}
+
+/// The per-system outputs of a flake, for all the systems it supports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiSystemSchema {
+    pub systems: BTreeMap<System, PerSystemSchema>,
+}
+
+impl MultiSystemSchema {
+    /// Which outputs exist on which system
+    ///
+    /// `legacyPackages` are excluded, as there are usually too many of them.
+    pub fn matrix(&self) -> Vec<MatrixRow> {
+        let mut rows: BTreeMap<(&str, &str), BTreeSet<System>> = BTreeMap::new();
+        for (system, schema) in &self.systems {
+            let trees = [
+                ("packages", &schema.packages),
+                ("devShells", &schema.devshells),
+                ("checks", &schema.checks),
+                ("apps", &schema.apps),
+            ];
+            for (output_type, tree) in trees {
+                for name in tree.keys() {
+                    rows.entry((output_type, name))
+                        .or_default()
+                        .insert(system.clone());
+                }
+            }
+            if schema.formatter.is_some() {
+                rows.entry(("formatter", ""))
+                    .or_default()
+                    .insert(system.clone());
+            }
+        }
+        rows.into_iter()
+            .map(|((output_type, name), systems)| MatrixRow {
+                output_type: output_type.to_string(),
+                name: name.to_string(),
+                systems,
+            })
+            .collect()
+    }
+}
+
+/// A row in [MultiSystemSchema::matrix]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatrixRow {
+    /// The flake output type (eg: `packages`)
+    pub output_type: String,
+    /// Name of the output (empty for `formatter`)
+    pub name: String,
+    /// The systems on which the output exists
+    pub systems: BTreeSet<System>,
+}
+
+impl MatrixRow {
+    /// The attribute path of this output, excluding the system
+    pub fn attr(&self) -> String {
+        if self.name.is_empty() {
+            self.output_type.clone()
+        } else {
+            format!("{}.{}", self.output_type, self.name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_systems() {
+        let val = |name: &str| serde_json::json!({ "what": "package", "derivationName": name, "shortDescription": null });
+        let output: FlakeOutputs = serde_json::from_value::<
+            crate::flake::outputs::FlakeOutputsUntagged,
+        >(serde_json::json!({
+            "packages": {
+                "output": {
+                    "children": {
+                        "x86_64-linux": { "children": { "foo": val("foo"), "bar": val("bar") } },
+                        "aarch64-darwin": { "children": { "foo": { "filtered": true } } }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+        .into_flake_outputs();
+        let schema = FlakeSchema::all_systems(&output);
+        let x86_64_linux = System::from("x86_64-linux");
+        let aarch64_darwin = System::from("aarch64-darwin");
+        assert_eq!(
+            schema.systems.keys().cloned().collect::<Vec<_>>(),
+            vec![aarch64_darwin.clone(), x86_64_linux.clone()]
+        );
+        assert_eq!(schema.systems[&x86_64_linux].packages.len(), 2);
+
+        let matrix = schema.matrix();
+        assert_eq!(
+            matrix.iter().map(|r| r.attr()).collect::<Vec<_>>(),
+            vec!["packages.bar", "packages.foo"]
+        );
+        assert_eq!(matrix[0].systems, BTreeSet::from([x86_64_linux.clone()]));
+        assert_eq!(
+            matrix[1].systems,
+            BTreeSet::from([aarch64_darwin, x86_64_linux])
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Canonical form of a [FlakeUrl], for comparing flake URLs that refer to the same flake
+use std::path::{Component, Path, PathBuf};
+
+use super::{
+    kind::{FlakeUrlKind, Forge},
+    FlakeUrl,
+};
+
+/// The ref naming the default branch of a repository, whatever it is called
+///
+/// Nix fetches the default branch when no ref is given. Branch names like
+/// `master` are not dropped, as they need not be the default branch.
+const DEFAULT_REF: &str = "HEAD";
+
+impl FlakeUrl {
+    /// Return the canonical form of this flake URL
+    ///
+    /// Relative paths are resolved against the current directory. See
+    /// [FlakeUrl::canonicalize_relative_to].
+    pub fn canonicalize(&self) -> FlakeUrl {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        self.canonicalize_relative_to(&cwd)
+    }
+
+    /// Return the canonical form of this flake URL, resolving relative paths
+    /// against `base`.
+    ///
+    /// Canonicalization,
+    /// - rewrites GitHub, GitLab and SourceHut web and git URLs to their forge
+    ///   URL (`github:owner/repo`), lowercasing the (case-insensitive) owner and
+    ///   repository of GitHub and GitLab,
+    /// - moves `ref` and `rev` query parameters of forge URLs into the path,
+    ///   and drops the `HEAD` ref (which names the default branch); branch
+    ///   names like `master` are kept, so `github:owner/repo/master` is not
+    ///   equivalent to `github:owner/repo`,
+    /// - removes trailing slashes, and makes paths absolute,
+    /// - spells out the type of `file` and `tarball` URLs (`file+https://..`),
+    /// - sorts the query parameters.
+    pub fn canonicalize_relative_to(&self, base: &Path) -> FlakeUrl {
+        let mut url = self.clone();
+        let forge_kind = match &url.kind {
+            FlakeUrlKind::File {
+                url,
+                explicit: false,
+            }
+            | FlakeUrlKind::Git { url, .. } => FlakeUrlKind::from_forge_web_url(url),
+            _ => None,
+        };
+        if let Some(kind) = forge_kind {
+            url.kind = kind;
+        }
+
+        let mut ref_param = url.take_param("ref");
+        let mut rev_param = url.take_param("rev");
+        let is_default_ref = |r: &String| r == DEFAULT_REF;
+        match &mut url.kind {
+            FlakeUrlKind::Forge {
+                forge,
+                owner,
+                repo,
+                ref_or_rev,
+            } => {
+                if matches!(forge, Forge::GitHub | Forge::GitLab) {
+                    *owner = owner.to_lowercase();
+                    *repo = repo.to_lowercase();
+                }
+                // Forge URLs carry these in their path
+                let r = rev_param
+                    .take()
+                    .or(ref_or_rev.take())
+                    .or(ref_param.take())
+                    .map(|r| r.trim_end_matches('/').to_string());
+                *ref_or_rev = r.filter(|r| !r.is_empty() && !is_default_ref(r));
+            }
+            FlakeUrlKind::Indirect { explicit, .. } => {
+                // The ref is kept, as a registry entry need not point to the
+                // default branch (eg: `nixpkgs` is nixpkgs-unstable)
+                *explicit = false;
+            }
+            FlakeUrlKind::Path { path, .. } => {
+                *path = absolute_path(base, Path::new(path)).display().to_string();
+            }
+            FlakeUrlKind::Git { url: u, .. } | FlakeUrlKind::Mercurial { url: u } => {
+                trim_trailing_slash(u);
+            }
+            FlakeUrlKind::File { url: u, explicit }
+            | FlakeUrlKind::Tarball { url: u, explicit } => {
+                trim_trailing_slash(u);
+                *explicit = true;
+            }
+        }
+        if let Some(r) = ref_param.filter(|r| !is_default_ref(r)) {
+            url.params.push(("ref".to_string(), r));
+        }
+        if let Some(r) = rev_param {
+            url.params.push(("rev".to_string(), r));
+        }
+        url.params.sort();
+        url
+    }
+
+    /// Whether both flake URLs refer to the same flake, by comparing their
+    /// canonical forms.
+    pub fn is_equivalent(&self, other: &FlakeUrl) -> bool {
+        self.canonicalize() == other.canonicalize()
+    }
+
+    /// The forge URL (`github:owner/repo`) equivalent to this web URL of a
+    /// repository (`https://github.com/owner/repo`), if any
+    ///
+    /// Nix does not recognize the latter as a flake repository.
+    pub fn to_forge_url(&self) -> Option<FlakeUrl> {
+        match &self.kind {
+            FlakeUrlKind::File {
+                url,
+                explicit: false,
+            } => Some(FlakeUrl {
+                kind: FlakeUrlKind::from_forge_web_url(url)?,
+                ..self.clone()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Remove the given query parameter, returning its value
+    fn take_param(&mut self, key: &str) -> Option<String> {
+        let i = self.params.iter().position(|(k, _)| k == key)?;
+        Some(self.params.remove(i).1)
+    }
+}
+
+impl FlakeUrlKind {
+    /// Convert a web or git URL of a repository on a known forge (eg:
+    /// `https://github.com/srid/emanote` or
+    /// `https://github.com/srid/emanote/tree/master`) to the corresponding
+    /// [FlakeUrlKind::Forge].
+    pub fn from_forge_web_url(url: &str) -> Option<Self> {
+        let rest = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))?;
+        let (host, path) = rest.split_once('/')?;
+        let forge = match host {
+            "github.com" => Forge::GitHub,
+            "gitlab.com" => Forge::GitLab,
+            "git.sr.ht" => Forge::SourceHut,
+            _ => return None,
+        };
+        let mut parts = path.trim_end_matches('/').splitn(3, '/');
+        let owner = parts.next().filter(|s| !s.is_empty())?;
+        let repo = parts.next().filter(|s| !s.is_empty())?;
+        let repo = repo.strip_suffix(".git").unwrap_or(repo);
+        let ref_or_rev = match parts.next() {
+            None => None,
+            Some(rest) => {
+                let rest = rest.strip_prefix("-/").unwrap_or(rest); // GitLab
+                let r = rest
+                    .strip_prefix("tree/")
+                    .or_else(|| rest.strip_prefix("commit/"))?;
+                Some(r.to_string())
+            }
+        };
+        Some(FlakeUrlKind::Forge {
+            forge,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            ref_or_rev,
+        })
+    }
+}
+
+/// Make the path absolute, resolving `.` and `..` lexically
+fn absolute_path(base: &Path, path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn trim_trailing_slash(s: &mut String) {
+    while s.ends_with('/') && !s.ends_with("://") {
+        s.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(s: &str) -> String {
+        s.parse::<FlakeUrl>()
+            .unwrap()
+            .canonicalize_relative_to(Path::new("/home/user"))
+            .to_string()
+    }
+
+    #[test]
+    fn test_forge_aliases() {
+        for s in [
+            "github:srid/emanote",
+            "github:srid/emanote/",
+            "github:srid/emanote/HEAD",
+            "github:Srid/Emanote",
+            "https://github.com/srid/emanote",
+            "git+https://github.com/srid/emanote.git",
+            "git+https://github.com/srid/emanote?ref=HEAD",
+        ] {
+            assert_eq!(canonical(s), "github:srid/emanote", "{}", s);
+        }
+        // Branches other than `HEAD` need not be the default branch
+        for s in [
+            "github:srid/emanote/master",
+            "github:srid/emanote?ref=master",
+            "https://github.com/srid/emanote/tree/master",
+            "git+https://github.com/srid/emanote?ref=master",
+        ] {
+            assert_eq!(canonical(s), "github:srid/emanote/master", "{}", s);
+        }
+        assert_eq!(
+            canonical("github:srid/emanote?ref=main"),
+            "github:srid/emanote/main"
+        );
+        assert_eq!(
+            canonical("github:srid/emanote?ref=dev"),
+            "github:srid/emanote/dev"
+        );
+        assert_eq!(
+            canonical("https://gitlab.com/foo/bar/-/tree/dev"),
+            "gitlab:foo/bar/dev"
+        );
+    }
+
+    #[test]
+    fn test_params_and_paths() {
+        assert_eq!(
+            canonical("git+https://example.org/repo/?rev=abc&dir=sub&ref=dev"),
+            "git+https://example.org/repo?dir=sub&ref=dev&rev=abc"
+        );
+        assert_eq!(canonical("."), "/home/user");
+        assert_eq!(canonical("./foo/../bar/"), "/home/user/bar");
+        assert_eq!(canonical("path:/nix/store/x/"), "path:/nix/store/x");
+        assert_eq!(canonical("flake:nixpkgs/master"), "nixpkgs/master");
+        assert_eq!(canonical("flake:nixpkgs"), "nixpkgs");
+        assert_eq!(
+            canonical("https://example.org/foo.tar.gz"),
+            "tarball+https://example.org/foo.tar.gz"
+        );
+        assert_eq!(
+            canonical("https://example.org/foo/"),
+            "file+https://example.org/foo"
+        );
+    }
+
+    #[test]
+    fn test_is_equivalent() {
+        let url = |s: &str| s.parse::<FlakeUrl>().unwrap();
+        assert!(url("github:srid/emanote").is_equivalent(&url("https://github.com/srid/emanote")));
+        assert!(!url("github:srid/emanote").is_equivalent(&url("github:srid/emanote/dev")));
+        assert!(url("https://example.org/foo").is_equivalent(&url("file+https://example.org/foo")));
+        // `master` need not be the default branch
+        assert!(!url("github:srid/emanote").is_equivalent(&url("github:srid/emanote/master")));
+        assert!(!url("github:srid/emanote/main").is_equivalent(&url("github:srid/emanote/master")));
+        assert!(!url("nixpkgs").is_equivalent(&url("nixpkgs/master")));
+        assert!(!url("github:srid/emanote").is_equivalent(&url("github:srid/emanote#foo")));
+    }
+}
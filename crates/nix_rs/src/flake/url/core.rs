@@ -7,18 +7,40 @@ use std::{
     str::FromStr,
 };
 
-use serde::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 
-use super::attr::FlakeAttr;
+use super::{attr::FlakeAttr, kind::FlakeUrlKind};
 
 /// A flake URL
 ///
 /// See [syntax here](https://nixos.org/manual/nix/stable/command-ref/new-cli/nix3-flake.html#url-like-syntax).
 ///
-/// Use `FromStr` to parse a string into a `FlakeUrl`. Or `From` or `Into` if
-/// you know the URL is valid.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct FlakeUrl(pub String);
+/// Use `FromStr` to parse a string into a `FlakeUrl`. The parsed URL displays
+/// back to the original string.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, SerializeDisplay, DeserializeFromStr,
+)]
+pub struct FlakeUrl {
+    /// The type of the flake reference, along with its location
+    pub kind: FlakeUrlKind,
+    /// Query parameters (`?key=value&..`), in their original order
+    pub params: Vec<(String, String)>,
+    /// The attribute part (`#..`)
+    pub attr: Option<String>,
+}
+
+/// Errors when parsing a [FlakeUrl]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum FlakeUrlError {
+    #[error("Empty string is not a valid Flake URL")]
+    Empty,
+
+    #[error("Missing owner or repository in flake URL: {0}")]
+    MissingRepo(String),
+
+    #[error("Unsupported flake URL type: {0}")]
+    UnsupportedType(String),
+}
 
 impl FlakeUrl {
     /// Provide real-world examples of flake URLs
@@ -40,75 +62,167 @@ impl FlakeUrl {
     /// Applicable only if the flake URL uses the [Path-like
     /// syntax](https://nixos.org/manual/nix/stable/command-ref/new-cli/nix3-flake.html#path-like-syntax)
     pub fn as_local_path(&self) -> Option<&Path> {
-        let s = self.0.strip_prefix("path:").unwrap_or(&self.0);
-        if s.starts_with('.') || s.starts_with('/') {
-            // Strip query (`?..`) and attrs (`#..`)
-            let s = s.split('?').next().unwrap_or(s);
-            let s = s.split('#').next().unwrap_or(s);
-            Some(Path::new(s))
-        } else {
-            None
+        match &self.kind {
+            FlakeUrlKind::Path { path, .. } => Some(Path::new(path)),
+            _ => None,
+        }
+    }
+
+    /// Get the value of the given query parameter
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set the given query parameter, replacing any existing value
+    pub fn set_param(&mut self, key: &str, value: String) {
+        match self.params.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.params.push((key.to_string(), value)),
+        }
+    }
+
+    /// The git branch or tag (`ref`) referenced by this URL
+    pub fn ref_(&self) -> Option<&str> {
+        match self.ref_or_rev_segment() {
+            Some(v) if !is_rev(v) => Some(v),
+            _ => self.param("ref"),
+        }
+    }
+
+    /// The git commit (`rev`) referenced by this URL
+    pub fn rev(&self) -> Option<&str> {
+        match &self.kind {
+            FlakeUrlKind::Indirect { rev: Some(rev), .. } => Some(rev),
+            _ => match self.ref_or_rev_segment() {
+                Some(v) if is_rev(v) => Some(v),
+                _ => self.param("rev"),
+            },
+        }
+    }
+
+    /// The subdirectory of the flake (`dir`) within its source
+    pub fn dir(&self) -> Option<&str> {
+        self.param("dir")
+    }
+
+    /// The `ref` or `rev` specified as path segment (`github:owner/repo/<ref-or-rev>`)
+    fn ref_or_rev_segment(&self) -> Option<&str> {
+        match &self.kind {
+            FlakeUrlKind::Forge { ref_or_rev, .. } | FlakeUrlKind::Indirect { ref_or_rev, .. } => {
+                ref_or_rev.as_deref()
+            }
+            _ => None,
         }
     }
 
     /// Split the [super::attr::FlakeAttr] out of the [FlakeUrl]
     pub fn split_attr(&self) -> (Self, FlakeAttr) {
-        match self.0.split_once('#') {
-            Some((url, attr)) => (FlakeUrl(url.to_string()), FlakeAttr(Some(attr.to_string()))),
-            None => (self.clone(), FlakeAttr(None)),
-        }
+        let url = FlakeUrl {
+            attr: None,
+            ..self.clone()
+        };
+        (url, FlakeAttr(self.attr.clone()))
     }
 
     /// Return the flake URL with the given attribute
     pub fn with_attr(&self, attr: &str) -> Self {
-        let (url, _) = self.split_attr();
-        FlakeUrl(format!("{}#{}", url.0, attr))
+        FlakeUrl {
+            attr: Some(attr.to_string()),
+            ..self.clone()
+        }
     }
 
     /// Return the flake URL pointing to the sub-flake
     pub fn sub_flake_url(&self, dir: String) -> FlakeUrl {
         if dir == "." {
-            self.clone()
-        } else if let Some(path) = self.as_local_path() {
+            return self.clone();
+        }
+        let mut url = self.clone();
+        if let Some(path) = self.as_local_path() {
             // Local path; just join the dir
-            let path_with_dir = path.join(dir);
-            FlakeUrl(format!("path:{}", path_with_dir.display()))
+            url.kind = FlakeUrlKind::Path {
+                path: path.join(dir).display().to_string(),
+                explicit: true,
+            };
         } else {
-            // Non-path URL; append `dir` query parameter
-            let mut url = self.0.clone();
-            if url.contains('?') {
-                url.push_str("&dir=");
-            } else {
-                url.push_str("?dir=");
-            }
-            url.push_str(&dir);
-            FlakeUrl(url)
+            // Non-path URL; set (or extend) the `dir` query parameter
+            let dir = match self.dir() {
+                Some(parent) => format!("{}/{}", parent, dir),
+                None => dir,
+            };
+            url.set_param("dir", dir);
+        }
+        url
+    }
+}
+
+/// Whether the given string looks like a git commit hash
+fn is_rev(s: &str) -> bool {
+    s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl From<FlakeUrlKind> for FlakeUrl {
+    fn from(kind: FlakeUrlKind) -> Self {
+        FlakeUrl {
+            kind,
+            params: vec![],
+            attr: None,
         }
     }
 }
 
 impl From<PathBuf> for FlakeUrl {
     fn from(path: PathBuf) -> Self {
-        FlakeUrl(format!("path:{}", path.display()))
+        FlakeUrlKind::Path {
+            path: path.display().to_string(),
+            explicit: true,
+        }
+        .into()
     }
 }
 
 impl FromStr for FlakeUrl {
-    type Err = String;
+    type Err = FlakeUrlError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim();
         if s.is_empty() {
-            Err("Empty string is not a valid Flake URL".to_string())
-        } else {
-            Ok(FlakeUrl(s.to_string()))
+            return Err(FlakeUrlError::Empty);
         }
+        let (s, attr) = match s.split_once('#') {
+            Some((s, attr)) => (s, Some(attr.to_string())),
+            None => (s, None),
+        };
+        let (s, query) = s.split_once('?').unwrap_or((s, ""));
+        let params = query
+            .split('&')
+            .filter(|kv| !kv.is_empty())
+            .map(|kv| {
+                let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+                (k.to_string(), v.to_string())
+            })
+            .collect();
+        Ok(FlakeUrl {
+            kind: FlakeUrlKind::parse(s)?,
+            params,
+            attr,
+        })
     }
 }
 
 impl Display for FlakeUrl {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.kind)?;
+        for (i, (k, v)) in self.params.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, k, v)?;
+        }
+        if let Some(attr) = &self.attr {
+            write!(f, "#{}", attr)?;
+        }
+        Ok(())
     }
 }
 
@@ -116,17 +230,21 @@ impl Display for FlakeUrl {
 mod tests {
     use super::*;
 
+    fn flake_url(s: &str) -> FlakeUrl {
+        s.parse().unwrap()
+    }
+
     #[test]
     fn test_flake_url_and_attr() {
-        let url = FlakeUrl("github:srid/nixci".to_string());
+        let url = flake_url("github:srid/nixci");
         assert_eq!(url.split_attr(), (url.clone(), FlakeAttr(None)));
         assert_eq!(url.split_attr().1.as_list(), [] as [&str; 0]);
 
-        let url = FlakeUrl("github:srid/nixci#extra-tests".to_string());
+        let url = flake_url("github:srid/nixci#extra-tests");
         assert_eq!(
             url.split_attr(),
             (
-                FlakeUrl("github:srid/nixci".to_string()),
+                flake_url("github:srid/nixci"),
                 FlakeAttr(Some("extra-tests".to_string()))
             )
         );
@@ -135,13 +253,10 @@ mod tests {
             vec!["extra-tests".to_string()]
         );
 
-        let url = FlakeUrl(".#foo.bar.qux".to_string());
+        let url = flake_url(".#foo.bar.qux");
         assert_eq!(
             url.split_attr(),
-            (
-                FlakeUrl(".".to_string()),
-                FlakeAttr(Some("foo.bar.qux".to_string()))
-            )
+            (flake_url("."), FlakeAttr(Some("foo.bar.qux".to_string())))
         );
         assert_eq!(
             url.split_attr().1.as_list(),
@@ -151,81 +266,178 @@ mod tests {
 
     #[test]
     fn test_as_local_flake() {
-        let url = FlakeUrl("github:srid/nixci".to_string());
+        let url = flake_url("github:srid/nixci");
         assert_eq!(url.as_local_path(), None);
 
-        let url = FlakeUrl(".".to_string());
+        let url = flake_url(".");
         assert_eq!(url.as_local_path().map(|p| p.to_str().unwrap()), Some("."));
 
-        let url = FlakeUrl("/foo".to_string());
+        let url = flake_url("/foo");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("/foo")));
 
-        let url = FlakeUrl("./foo?q=bar".to_string());
+        let url = flake_url("./foo?q=bar");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("./foo")));
 
-        let url = FlakeUrl("./foo#attr".to_string());
+        let url = flake_url("./foo#attr");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("./foo")));
 
-        let url = FlakeUrl("/foo?q=bar#attr".to_string());
+        let url = flake_url("/foo?q=bar#attr");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("/foo")));
 
-        let url = FlakeUrl("path:.".to_string());
+        let url = flake_url("path:.");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new(".")));
 
-        let url = FlakeUrl("path:./foo".to_string());
+        let url = flake_url("path:./foo");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("./foo")));
 
-        let url = FlakeUrl("path:./foo?q=bar".to_string());
+        let url = flake_url("path:./foo?q=bar");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("./foo")));
 
-        let url = FlakeUrl("path:./foo#attr".to_string());
+        let url = flake_url("path:./foo#attr");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("./foo")));
 
-        let url = FlakeUrl("path:/foo?q=bar#attr".to_string());
+        let url = flake_url("path:/foo?q=bar#attr");
         assert_eq!(url.as_local_path(), Some(std::path::Path::new("/foo")));
     }
 
     #[test]
     fn test_sub_flake_url() {
         // Path refs
-        let url = FlakeUrl(".".to_string());
+        let url = flake_url(".");
         assert_eq!(url.sub_flake_url(".".to_string()), url.clone());
         assert_eq!(
             url.sub_flake_url("sub".to_string()),
-            FlakeUrl("path:./sub".to_string())
+            flake_url("path:./sub")
         );
 
         // URI refs
-        let url = FlakeUrl("github:srid/nixci".to_string());
+        let url = flake_url("github:srid/nixci");
         assert_eq!(url.sub_flake_url(".".to_string()), url.clone());
         assert_eq!(
             url.sub_flake_url("dev".to_string()),
-            FlakeUrl("github:srid/nixci?dir=dev".to_string())
+            flake_url("github:srid/nixci?dir=dev")
         );
     }
 
     #[test]
     fn test_sub_flake_url_with_query() {
-        let url = FlakeUrl("git+https://example.org/my/repo?ref=master".to_string());
+        let url = flake_url("git+https://example.org/my/repo?ref=master");
         assert_eq!(url.sub_flake_url(".".to_string()), url.clone());
         assert_eq!(
             url.sub_flake_url("dev".to_string()),
-            FlakeUrl("git+https://example.org/my/repo?ref=master&dir=dev".to_string())
+            flake_url("git+https://example.org/my/repo?ref=master&dir=dev")
         );
     }
 
     #[test]
     fn test_with_attr() {
-        let url = FlakeUrl("github:srid/nixci".to_string());
+        let url = flake_url("github:srid/nixci");
+        assert_eq!(url.with_attr("foo"), flake_url("github:srid/nixci#foo"));
+
+        let url: FlakeUrl = "github:srid/nixci#foo".parse().unwrap();
+        assert_eq!(url.with_attr("bar"), flake_url("github:srid/nixci#bar"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for s in [
+            ".",
+            "./foo?dir=bar#attr",
+            "/foo",
+            "path:/foo",
+            "github:srid/nixci",
+            "github:srid/nixci/main?dir=sub#default",
+            "gitlab:veloren/veloren/v0.1",
+            "sourcehut:~misterio/nix-colors",
+            "git+https://example.org/my/repo?ref=master&rev=abc",
+            "git+ssh://git@github.com/srid/nixci",
+            "git+file:///home/user/repo",
+            "git://example.org/repo",
+            "hg+https://example.org/repo",
+            "tarball+https://example.org/foo",
+            "https://example.org/foo.tar.gz",
+            "file+https://example.org/foo",
+            "nixpkgs",
+            "nixpkgs/nixos-23.11",
+            "flake:nixpkgs",
+        ] {
+            assert_eq!(flake_url(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(
+            flake_url("github:srid/nixci/main").kind,
+            FlakeUrlKind::Forge {
+                forge: crate::flake::url::kind::Forge::GitHub,
+                owner: "srid".to_string(),
+                repo: "nixci".to_string(),
+                ref_or_rev: Some("main".to_string()),
+            }
+        );
+        assert!(matches!(
+            flake_url("https://example.org/foo.zip").kind,
+            FlakeUrlKind::Tarball {
+                explicit: false,
+                ..
+            }
+        ));
+        assert!(matches!(
+            flake_url("https://example.org/flake.nix").kind,
+            FlakeUrlKind::File {
+                explicit: false,
+                ..
+            }
+        ));
+        assert!(matches!(
+            flake_url("nixpkgs").kind,
+            FlakeUrlKind::Indirect { .. }
+        ));
+        assert_eq!("".parse::<FlakeUrl>(), Err(FlakeUrlError::Empty));
+        assert!(matches!(
+            "github:srid".parse::<FlakeUrl>(),
+            Err(FlakeUrlError::MissingRepo(_))
+        ));
         assert_eq!(
-            url.with_attr("foo"),
-            FlakeUrl("github:srid/nixci#foo".to_string())
+            "foo:bar".parse::<FlakeUrl>(),
+            Err(FlakeUrlError::UnsupportedType("foo".to_string()))
         );
+    }
 
-        let url: FlakeUrl = "github:srid/nixci#foo".parse().unwrap();
+    #[test]
+    fn test_ref_rev_dir() {
+        let url = flake_url("github:srid/nixci/main?dir=sub");
+        assert_eq!(url.ref_(), Some("main"));
+        assert_eq!(url.rev(), None);
+        assert_eq!(url.dir(), Some("sub"));
+
+        let rev = "c85563721c388629fa9e538a1d97274861bc8321";
+        let url = flake_url(&format!("github:srid/nixci/{}", rev));
+        assert_eq!(url.ref_(), None);
+        assert_eq!(url.rev(), Some(rev));
+
+        let url = flake_url("git+https://example.org/repo?ref=master&rev=abc");
+        assert_eq!(url.ref_(), Some("master"));
+        assert_eq!(url.rev(), Some("abc"));
+
+        let url = flake_url(&format!("nixpkgs/nixos-23.11/{}", rev));
+        assert_eq!(url.ref_(), Some("nixos-23.11"));
+        assert_eq!(url.rev(), Some(rev));
+    }
+
+    #[test]
+    fn test_sub_flake_url_path_with_query() {
+        let url = flake_url("path:./foo?lastModified=1#attr");
+        assert_eq!(
+            url.sub_flake_url("sub".to_string()),
+            flake_url("path:./foo/sub?lastModified=1#attr")
+        );
+
+        let url = flake_url("github:srid/nixci?dir=a");
         assert_eq!(
-            url.with_attr("bar"),
-            FlakeUrl("github:srid/nixci#bar".to_string())
+            url.sub_flake_url("b".to_string()),
+            flake_url("github:srid/nixci?dir=a/b")
         );
     }
 }
@@ -0,0 +1,241 @@
+//! The fetcher-specific part of a [super::FlakeUrl]
+//!
+//! See <https://nixos.org/manual/nix/stable/command-ref/new-cli/nix3-flake.html#types>
+use std::fmt::{Display, Formatter};
+
+use super::FlakeUrlError;
+
+/// Git forges with a dedicated URL syntax (`github:owner/repo`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+    SourceHut,
+}
+
+impl Forge {
+    /// The URL scheme of this forge (eg: `github`)
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Forge::GitHub => "github",
+            Forge::GitLab => "gitlab",
+            Forge::SourceHut => "sourcehut",
+        }
+    }
+}
+
+/// The type of a [super::FlakeUrl], along with its type-specific location
+///
+/// Query parameters and the attribute are not part of this; see
+/// [super::FlakeUrl].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FlakeUrlKind {
+    /// `github:owner/repo/ref-or-rev` (likewise for `gitlab:` and `sourcehut:`)
+    Forge {
+        forge: Forge,
+        owner: String,
+        repo: String,
+        ref_or_rev: Option<String>,
+    },
+    /// `git+https://…`, `git+ssh://…`, `git+file://…`, or a bare `git://…`
+    Git {
+        /// The URL, without the `git+` prefix
+        url: String,
+        /// Whether the `git+` prefix is present
+        explicit: bool,
+    },
+    /// `hg+https://…`, etc.
+    Mercurial {
+        /// The URL, without the `hg+` prefix
+        url: String,
+    },
+    /// `path:./foo`, or a bare path like `.` or `/foo`
+    Path {
+        path: String,
+        /// Whether the `path:` prefix is present
+        explicit: bool,
+    },
+    /// `tarball+https://…`, or a bare URL to an archive
+    Tarball {
+        url: String,
+        /// Whether the `tarball+` prefix is present
+        explicit: bool,
+    },
+    /// `file+https://…`, or a bare URL to a file that is not an archive
+    File {
+        url: String,
+        /// Whether the `file+` prefix is present
+        explicit: bool,
+    },
+    /// `flake:nixpkgs/ref/rev`, or a bare flake registry id like `nixpkgs`
+    Indirect {
+        id: String,
+        ref_or_rev: Option<String>,
+        rev: Option<String>,
+        /// Whether the `flake:` prefix is present
+        explicit: bool,
+    },
+}
+
+/// File extensions that make a bare URL a [FlakeUrlKind::Tarball]
+const ARCHIVE_EXTENSIONS: &[&str] = &[
+    ".zip", ".tar", ".tgz", ".tar.gz", ".tar.xz", ".tar.bz2", ".tar.zst",
+];
+
+impl FlakeUrlKind {
+    /// Parse the part of a flake URL that precedes the query and attribute
+    pub(crate) fn parse(s: &str) -> Result<Self, FlakeUrlError> {
+        if s.starts_with('.') || s.starts_with('/') {
+            return Ok(FlakeUrlKind::Path {
+                path: s.to_string(),
+                explicit: false,
+            });
+        }
+        let Some((scheme, rest)) = s.split_once(':') else {
+            return Ok(Self::parse_bare_id(s));
+        };
+        let forge = match scheme {
+            "github" => Some(Forge::GitHub),
+            "gitlab" => Some(Forge::GitLab),
+            "sourcehut" => Some(Forge::SourceHut),
+            _ => None,
+        };
+        if let Some(forge) = forge {
+            let mut parts = rest.splitn(3, '/');
+            let (Some(owner), Some(repo)) = (parts.next(), parts.next()) else {
+                return Err(FlakeUrlError::MissingRepo(s.to_string()));
+            };
+            if owner.is_empty() || repo.is_empty() {
+                return Err(FlakeUrlError::MissingRepo(s.to_string()));
+            }
+            return Ok(FlakeUrlKind::Forge {
+                forge,
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                ref_or_rev: parts.next().map(|s| s.to_string()),
+            });
+        }
+        let kind = match scheme {
+            "path" => FlakeUrlKind::Path {
+                path: rest.to_string(),
+                explicit: true,
+            },
+            "flake" => {
+                let mut parts = rest.splitn(3, '/').map(|s| s.to_string());
+                FlakeUrlKind::Indirect {
+                    id: parts.next().unwrap_or_default(),
+                    ref_or_rev: parts.next(),
+                    rev: parts.next(),
+                    explicit: true,
+                }
+            }
+            "git" => FlakeUrlKind::Git {
+                url: s.to_string(),
+                explicit: false,
+            },
+            _ if rest.starts_with("//") => {
+                if let Some(url) = s.strip_prefix("git+") {
+                    FlakeUrlKind::Git {
+                        url: url.to_string(),
+                        explicit: true,
+                    }
+                } else if let Some(url) = s.strip_prefix("hg+") {
+                    FlakeUrlKind::Mercurial {
+                        url: url.to_string(),
+                    }
+                } else if let Some(url) = s.strip_prefix("tarball+") {
+                    FlakeUrlKind::Tarball {
+                        url: url.to_string(),
+                        explicit: true,
+                    }
+                } else if let Some(url) = s.strip_prefix("file+") {
+                    FlakeUrlKind::File {
+                        url: url.to_string(),
+                        explicit: true,
+                    }
+                } else if !matches!(scheme, "http" | "https" | "file") {
+                    return Err(FlakeUrlError::UnsupportedType(scheme.to_string()));
+                } else if ARCHIVE_EXTENSIONS.iter().any(|ext| s.ends_with(ext)) {
+                    FlakeUrlKind::Tarball {
+                        url: s.to_string(),
+                        explicit: false,
+                    }
+                } else {
+                    FlakeUrlKind::File {
+                        url: s.to_string(),
+                        explicit: false,
+                    }
+                }
+            }
+            _ => return Err(FlakeUrlError::UnsupportedType(scheme.to_string())),
+        };
+        Ok(kind)
+    }
+
+    /// Parse a bare registry id (`nixpkgs/ref/rev`), falling back to a
+    /// relative path if it is not a valid id.
+    fn parse_bare_id(s: &str) -> Self {
+        let mut parts = s.splitn(3, '/').map(|s| s.to_string());
+        let id = parts.next().unwrap_or_default();
+        let is_valid_id = id.starts_with(|c: char| c.is_ascii_alphabetic())
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if is_valid_id {
+            FlakeUrlKind::Indirect {
+                id,
+                ref_or_rev: parts.next(),
+                rev: parts.next(),
+                explicit: false,
+            }
+        } else {
+            FlakeUrlKind::Path {
+                path: s.to_string(),
+                explicit: false,
+            }
+        }
+    }
+}
+
+impl Display for FlakeUrlKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let prefix = |explicit: &bool, prefix: &'static str| if *explicit { prefix } else { "" };
+        match self {
+            FlakeUrlKind::Forge {
+                forge,
+                owner,
+                repo,
+                ref_or_rev,
+            } => {
+                write!(f, "{}:{}/{}", forge.scheme(), owner, repo)?;
+                if let Some(v) = ref_or_rev {
+                    write!(f, "/{}", v)?;
+                }
+                Ok(())
+            }
+            FlakeUrlKind::Git { url, explicit } => write!(f, "{}{}", prefix(explicit, "git+"), url),
+            FlakeUrlKind::Mercurial { url } => write!(f, "hg+{}", url),
+            FlakeUrlKind::Path { path, explicit } => {
+                write!(f, "{}{}", prefix(explicit, "path:"), path)
+            }
+            FlakeUrlKind::Tarball { url, explicit } => {
+                write!(f, "{}{}", prefix(explicit, "tarball+"), url)
+            }
+            FlakeUrlKind::File { url, explicit } => {
+                write!(f, "{}{}", prefix(explicit, "file+"), url)
+            }
+            FlakeUrlKind::Indirect {
+                id,
+                ref_or_rev,
+                rev,
+                explicit,
+            } => {
+                write!(f, "{}{}", prefix(explicit, "flake:"), id)?;
+                for v in [ref_or_rev, rev].into_iter().flatten() {
+                    write!(f, "/{}", v)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
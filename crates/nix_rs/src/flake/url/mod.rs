@@ -1,5 +1,7 @@
 pub mod attr;
+mod canonical;
 mod core;
+pub mod kind;
 pub mod qualified_attr;
 
 pub use core::*;
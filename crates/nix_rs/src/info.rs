@@ -2,7 +2,7 @@
 use serde::{Deserialize, Serialize};
 use tokio::sync::OnceCell;
 
-use crate::{config::NixConfig, env::NixEnv, version::NixVersion};
+use crate::{command::NixCmd, config::NixConfig, env::NixEnv, version::NixVersion};
 
 /// All the information about the user's Nix installation
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -29,7 +29,7 @@ impl NixInfo {
 
     /// Determine [NixInfo] on the user's system
     pub async fn new(nix_config: NixConfig) -> Result<NixInfo, NixInfoError> {
-        let nix_version = NixVersion::from_nix().await?;
+        let nix_version = NixVersion::from_nix(&NixCmd::default()).await?;
         let nix_env = NixEnv::detect().await?;
         Ok(NixInfo {
             nix_version,
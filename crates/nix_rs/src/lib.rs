@@ -2,10 +2,14 @@
 //!
 //! This crate exposes various types representing what nix command gives us,
 //! along with a `from_nix` command to evaluate them.
+pub mod build;
 pub mod command;
 pub mod config;
+pub mod derivation;
 pub mod env;
 pub mod flake;
 pub mod info;
+pub mod log;
 pub mod refs;
+pub mod store;
 pub mod version;
@@ -0,0 +1,601 @@
+//! Rust module for Nix's structured logs (`--log-format internal-json`)
+//!
+//! In this log format, Nix writes each log event as a JSON object on its own
+//! stderr line, prefixed by `@nix `. See
+//! [logging.cc](https://github.com/NixOS/nix/blob/master/src/libutil/logging.cc)
+//! for the producing side.
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio_stream::{wrappers::LinesStream, Stream, StreamExt};
+
+/// Prefix of every structured log line
+const LOG_PREFIX: &str = "@nix ";
+
+/// A line of stderr produced by a nix command run with `--log-format internal-json`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogLine {
+    /// A structured log event
+    Event(LogEvent),
+    /// Any other line (e.g. written by a process bypassing Nix's logger)
+    Text(String),
+}
+
+impl LogLine {
+    /// Parse a single line of stderr
+    pub fn parse(line: &str) -> Result<Self, serde_json::Error> {
+        match line.strip_prefix(LOG_PREFIX) {
+            Some(json) => {
+                let raw = serde_json::from_str::<RawLogEvent>(json)?;
+                Ok(LogLine::Event(raw.into()))
+            }
+            None => Ok(LogLine::Text(line.to_string())),
+        }
+    }
+}
+
+/// Return a [Stream] of [LogLine]s parsed from the given reader (typically the
+/// stderr of a nix process)
+pub fn log_stream<R>(reader: R) -> impl Stream<Item = Result<LogLine, LogError>>
+where
+    R: AsyncRead + Unpin,
+{
+    LinesStream::new(BufReader::new(reader).lines()).map(|line| Ok(LogLine::parse(&line?)?))
+}
+
+/// Identifier of an activity, unique within a nix process
+pub type ActivityId = u64;
+
+/// A structured log event emitted by Nix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogEvent {
+    /// An activity (build, download, ..) has started
+    Start {
+        id: ActivityId,
+        level: Verbosity,
+        /// The parent activity, if any
+        parent: Option<ActivityId>,
+        /// Human-readable description of the activity
+        text: String,
+        activity: Activity,
+    },
+    /// An activity has stopped
+    Stop { id: ActivityId },
+    /// An activity reported an intermediate result
+    Result {
+        id: ActivityId,
+        result: ActivityResult,
+    },
+    /// A log message that is not tied to any activity
+    Msg { level: Verbosity, msg: String },
+}
+
+/// An activity started by Nix, along with the information specific to its type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Activity {
+    /// Copying a store path from one store to another
+    CopyPath {
+        path: String,
+        from: String,
+        to: String,
+    },
+    /// Downloading (or uploading) a file
+    FileTransfer {
+        uri: String,
+    },
+    /// Realising a set of store paths
+    Realise,
+    /// Copying a set of store paths
+    CopyPaths,
+    /// Building a set of derivations
+    Builds,
+    /// Building a single derivation
+    Build {
+        drv_path: String,
+        /// The remote builder doing the build; `None` if built locally
+        machine: Option<String>,
+    },
+    OptimiseStore,
+    VerifyPaths,
+    /// Fetching a store path from a substituter
+    Substitute {
+        path: String,
+        uri: String,
+    },
+    /// Querying a substituter for information on a store path
+    QueryPathInfo {
+        path: String,
+        uri: String,
+    },
+    /// Running the post-build-hook for a derivation
+    PostBuildHook {
+        drv_path: String,
+    },
+    /// Waiting for a build slot or lock
+    BuildWaiting,
+    /// Fetching a flake input or other source tree
+    FetchTree,
+    /// An activity that is not (yet) recognized by this module, or whose
+    /// fields did not match the expected shape
+    Other {
+        type_: ActivityType,
+        fields: Vec<Field>,
+    },
+}
+
+impl Activity {
+    fn new(type_: ActivityType, fields: Vec<Field>) -> Self {
+        let typed = match type_ {
+            ActivityType::CopyPath => (|| {
+                Some(Activity::CopyPath {
+                    path: str_field(&fields, 0)?,
+                    from: str_field(&fields, 1)?,
+                    to: str_field(&fields, 2)?,
+                })
+            })(),
+            ActivityType::FileTransfer => {
+                str_field(&fields, 0).map(|uri| Activity::FileTransfer { uri })
+            }
+            ActivityType::Realise => Some(Activity::Realise),
+            ActivityType::CopyPaths => Some(Activity::CopyPaths),
+            ActivityType::Builds => Some(Activity::Builds),
+            ActivityType::Build => str_field(&fields, 0).map(|drv_path| Activity::Build {
+                drv_path,
+                machine: str_field(&fields, 1).filter(|s| !s.is_empty()),
+            }),
+            ActivityType::OptimiseStore => Some(Activity::OptimiseStore),
+            ActivityType::VerifyPaths => Some(Activity::VerifyPaths),
+            ActivityType::Substitute => (|| {
+                Some(Activity::Substitute {
+                    path: str_field(&fields, 0)?,
+                    uri: str_field(&fields, 1)?,
+                })
+            })(),
+            ActivityType::QueryPathInfo => (|| {
+                Some(Activity::QueryPathInfo {
+                    path: str_field(&fields, 0)?,
+                    uri: str_field(&fields, 1)?,
+                })
+            })(),
+            ActivityType::PostBuildHook => {
+                str_field(&fields, 0).map(|drv_path| Activity::PostBuildHook { drv_path })
+            }
+            ActivityType::BuildWaiting => Some(Activity::BuildWaiting),
+            ActivityType::FetchTree => Some(Activity::FetchTree),
+            ActivityType::Unknown | ActivityType::Other(_) => None,
+        };
+        typed.unwrap_or(Activity::Other { type_, fields })
+    }
+}
+
+/// An intermediate result reported by an [Activity]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActivityResult {
+    /// A file was hard-linked while optimising the store
+    FileLinked {
+        bytes: u64,
+        blocks: u64,
+    },
+    /// A line of build output
+    BuildLogLine(String),
+    UntrustedPath(String),
+    CorruptedPath(String),
+    /// The build entered a new phase (e.g. `buildPhase`)
+    SetPhase(String),
+    /// Progress of the activity
+    Progress(Progress),
+    /// The expected number of sub-activities of the given type
+    SetExpected {
+        activity_type: ActivityType,
+        expected: u64,
+    },
+    /// A line of output from the post-build-hook
+    PostBuildLogLine(String),
+    FetchStatus(String),
+    /// A result that is not (yet) recognized by this module, or whose fields
+    /// did not match the expected shape
+    Other {
+        type_: ResultType,
+        fields: Vec<Field>,
+    },
+}
+
+impl ActivityResult {
+    fn new(type_: ResultType, fields: Vec<Field>) -> Self {
+        let typed = match type_ {
+            ResultType::FileLinked => (|| {
+                Some(ActivityResult::FileLinked {
+                    bytes: int_field(&fields, 0)?,
+                    blocks: int_field(&fields, 1)?,
+                })
+            })(),
+            ResultType::BuildLogLine => str_field(&fields, 0).map(ActivityResult::BuildLogLine),
+            ResultType::UntrustedPath => str_field(&fields, 0).map(ActivityResult::UntrustedPath),
+            ResultType::CorruptedPath => str_field(&fields, 0).map(ActivityResult::CorruptedPath),
+            ResultType::SetPhase => str_field(&fields, 0).map(ActivityResult::SetPhase),
+            ResultType::Progress => (|| {
+                Some(ActivityResult::Progress(Progress {
+                    done: int_field(&fields, 0)?,
+                    expected: int_field(&fields, 1)?,
+                    running: int_field(&fields, 2)?,
+                    failed: int_field(&fields, 3)?,
+                }))
+            })(),
+            ResultType::SetExpected => (|| {
+                Some(ActivityResult::SetExpected {
+                    activity_type: int_field(&fields, 0)?.into(),
+                    expected: int_field(&fields, 1)?,
+                })
+            })(),
+            ResultType::PostBuildLogLine => {
+                str_field(&fields, 0).map(ActivityResult::PostBuildLogLine)
+            }
+            ResultType::FetchStatus => str_field(&fields, 0).map(ActivityResult::FetchStatus),
+            ResultType::Other(_) => None,
+        };
+        typed.unwrap_or(ActivityResult::Other { type_, fields })
+    }
+}
+
+/// Progress of an activity
+///
+/// For file transfers, the numbers are in bytes; for builds and copies, they
+/// count the sub-activities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    pub done: u64,
+    pub expected: u64,
+    pub running: u64,
+    pub failed: u64,
+}
+
+/// The type of an [Activity], as numbered by Nix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub enum ActivityType {
+    Unknown,
+    CopyPath,
+    FileTransfer,
+    Realise,
+    CopyPaths,
+    Builds,
+    Build,
+    OptimiseStore,
+    VerifyPaths,
+    Substitute,
+    QueryPathInfo,
+    PostBuildHook,
+    BuildWaiting,
+    FetchTree,
+    /// A type introduced in a Nix version newer than this module
+    Other(u64),
+}
+
+impl From<u64> for ActivityType {
+    fn from(n: u64) -> Self {
+        match n {
+            0 => Self::Unknown,
+            100 => Self::CopyPath,
+            101 => Self::FileTransfer,
+            102 => Self::Realise,
+            103 => Self::CopyPaths,
+            104 => Self::Builds,
+            105 => Self::Build,
+            106 => Self::OptimiseStore,
+            107 => Self::VerifyPaths,
+            108 => Self::Substitute,
+            109 => Self::QueryPathInfo,
+            110 => Self::PostBuildHook,
+            111 => Self::BuildWaiting,
+            112 => Self::FetchTree,
+            n => Self::Other(n),
+        }
+    }
+}
+
+impl From<ActivityType> for u64 {
+    fn from(t: ActivityType) -> Self {
+        match t {
+            ActivityType::Unknown => 0,
+            ActivityType::CopyPath => 100,
+            ActivityType::FileTransfer => 101,
+            ActivityType::Realise => 102,
+            ActivityType::CopyPaths => 103,
+            ActivityType::Builds => 104,
+            ActivityType::Build => 105,
+            ActivityType::OptimiseStore => 106,
+            ActivityType::VerifyPaths => 107,
+            ActivityType::Substitute => 108,
+            ActivityType::QueryPathInfo => 109,
+            ActivityType::PostBuildHook => 110,
+            ActivityType::BuildWaiting => 111,
+            ActivityType::FetchTree => 112,
+            ActivityType::Other(n) => n,
+        }
+    }
+}
+
+/// The type of an [ActivityResult], as numbered by Nix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub enum ResultType {
+    FileLinked,
+    BuildLogLine,
+    UntrustedPath,
+    CorruptedPath,
+    SetPhase,
+    Progress,
+    SetExpected,
+    PostBuildLogLine,
+    FetchStatus,
+    /// A type introduced in a Nix version newer than this module
+    Other(u64),
+}
+
+impl From<u64> for ResultType {
+    fn from(n: u64) -> Self {
+        match n {
+            100 => Self::FileLinked,
+            101 => Self::BuildLogLine,
+            102 => Self::UntrustedPath,
+            103 => Self::CorruptedPath,
+            104 => Self::SetPhase,
+            105 => Self::Progress,
+            106 => Self::SetExpected,
+            107 => Self::PostBuildLogLine,
+            108 => Self::FetchStatus,
+            n => Self::Other(n),
+        }
+    }
+}
+
+impl From<ResultType> for u64 {
+    fn from(t: ResultType) -> Self {
+        match t {
+            ResultType::FileLinked => 100,
+            ResultType::BuildLogLine => 101,
+            ResultType::UntrustedPath => 102,
+            ResultType::CorruptedPath => 103,
+            ResultType::SetPhase => 104,
+            ResultType::Progress => 105,
+            ResultType::SetExpected => 106,
+            ResultType::PostBuildLogLine => 107,
+            ResultType::FetchStatus => 108,
+            ResultType::Other(n) => n,
+        }
+    }
+}
+
+/// Verbosity level of a log event
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize_repr, Deserialize_repr,
+)]
+#[repr(u8)]
+pub enum Verbosity {
+    Error = 0,
+    Warn = 1,
+    Notice = 2,
+    Info = 3,
+    Talkative = 4,
+    Chatty = 5,
+    Debug = 6,
+    Vomit = 7,
+}
+
+/// An untyped field of a start or result event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Field {
+    Int(u64),
+    String(String),
+}
+
+fn str_field(fields: &[Field], idx: usize) -> Option<String> {
+    match fields.get(idx)? {
+        Field::String(s) => Some(s.clone()),
+        Field::Int(_) => None,
+    }
+}
+
+fn int_field(fields: &[Field], idx: usize) -> Option<u64> {
+    match fields.get(idx)? {
+        Field::Int(n) => Some(*n),
+        Field::String(_) => None,
+    }
+}
+
+/// The JSON representation of [LogEvent], as written by Nix
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+enum RawLogEvent {
+    Start {
+        id: ActivityId,
+        level: Verbosity,
+        #[serde(default)]
+        parent: ActivityId,
+        #[serde(default)]
+        text: String,
+        #[serde(rename = "type")]
+        type_: ActivityType,
+        #[serde(default)]
+        fields: Vec<Field>,
+    },
+    Stop {
+        id: ActivityId,
+    },
+    Result {
+        id: ActivityId,
+        #[serde(rename = "type")]
+        type_: ResultType,
+        #[serde(default)]
+        fields: Vec<Field>,
+    },
+    Msg {
+        level: Verbosity,
+        msg: String,
+    },
+}
+
+impl From<RawLogEvent> for LogEvent {
+    fn from(raw: RawLogEvent) -> Self {
+        match raw {
+            RawLogEvent::Start {
+                id,
+                level,
+                parent,
+                text,
+                type_,
+                fields,
+            } => LogEvent::Start {
+                id,
+                level,
+                // Nix uses 0 to denote the absence of a parent
+                parent: (parent != 0).then_some(parent),
+                text,
+                activity: Activity::new(type_, fields),
+            },
+            RawLogEvent::Stop { id } => LogEvent::Stop { id },
+            RawLogEvent::Result { id, type_, fields } => LogEvent::Result {
+                id,
+                result: ActivityResult::new(type_, fields),
+            },
+            RawLogEvent::Msg { level, msg } => LogEvent::Msg { level, msg },
+        }
+    }
+}
+
+/// Errors when reading the structured logs of a nix command
+#[derive(Error, Debug)]
+pub enum LogError {
+    #[error("Failed to read nix stderr: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to parse nix log event: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Captured from `nix build --log-format internal-json nixpkgs#hello --rebuild`
+    const BUILD_LOG: &str = r#"@nix {"action":"start","id":14133337194496,"level":4,"parent":0,"text":"querying info about missing paths","type":0}
+@nix {"action":"stop","id":14133337194496}
+@nix {"action":"start","id":14133337194498,"level":5,"parent":0,"text":"","type":104}
+@nix {"action":"result","fields":[104,1],"id":14133337194498,"type":106}
+@nix {"action":"start","fields":["/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-hello-2.12.1.drv","",1,1],"id":14133337194499,"level":3,"parent":14133337194498,"text":"building '/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-hello-2.12.1.drv'","type":105}
+@nix {"action":"result","fields":[0,1,1,0],"id":14133337194498,"type":105}
+@nix {"action":"result","fields":["unpackPhase"],"id":14133337194499,"type":104}
+@nix {"action":"result","fields":["unpacking source archive /nix/store/pa10z4ngm0g83kx9mssrqzz30s84vq7k-hello-2.12.1.tar.gz"],"id":14133337194499,"type":101}
+@nix {"action":"start","fields":["https://cache.nixos.org/nar/1a2b.nar.xz"],"id":14133337194500,"level":4,"parent":0,"text":"downloading 'https://cache.nixos.org/nar/1a2b.nar.xz'","type":101}
+@nix {"action":"result","fields":[40960,121520,0,0],"id":14133337194500,"type":105}
+@nix {"action":"msg","level":0,"msg":"error: builder for '/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-hello-2.12.1.drv' failed with exit code 2"}
+some stray output
+@nix {"action":"result","fields":["foo"],"id":1,"type":199}
+@nix {"action":"start","fields":[],"id":14133337194501,"level":3,"parent":0,"text":"","type":105}"#;
+
+    fn parse_all(s: &str) -> Vec<LogLine> {
+        s.lines().map(|l| LogLine::parse(l).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_build_log() {
+        let lines = parse_all(BUILD_LOG);
+        assert_eq!(lines.len(), 14);
+        assert_eq!(
+            lines[1],
+            LogLine::Event(LogEvent::Stop { id: 14133337194496 })
+        );
+        assert_eq!(
+            lines[3],
+            LogLine::Event(LogEvent::Result {
+                id: 14133337194498,
+                result: ActivityResult::SetExpected {
+                    activity_type: ActivityType::Builds,
+                    expected: 1
+                }
+            })
+        );
+        assert_eq!(
+            lines[4],
+            LogLine::Event(LogEvent::Start {
+                id: 14133337194499,
+                level: Verbosity::Info,
+                parent: Some(14133337194498),
+                text: "building '/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-hello-2.12.1.drv'"
+                    .to_string(),
+                activity: Activity::Build {
+                    drv_path: "/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-hello-2.12.1.drv"
+                        .to_string(),
+                    machine: None
+                }
+            })
+        );
+        assert_eq!(
+            lines[6],
+            LogLine::Event(LogEvent::Result {
+                id: 14133337194499,
+                result: ActivityResult::SetPhase("unpackPhase".to_string())
+            })
+        );
+        assert_eq!(
+            lines[9],
+            LogLine::Event(LogEvent::Result {
+                id: 14133337194500,
+                result: ActivityResult::Progress(Progress {
+                    done: 40960,
+                    expected: 121520,
+                    running: 0,
+                    failed: 0
+                })
+            })
+        );
+        assert!(matches!(
+            &lines[10],
+            LogLine::Event(LogEvent::Msg { level: Verbosity::Error, msg }) if msg.starts_with("error: builder")
+        ));
+        assert_eq!(lines[11], LogLine::Text("some stray output".to_string()));
+    }
+
+    #[test]
+    fn test_parse_unrecognized() {
+        let lines = parse_all(BUILD_LOG);
+        // Unknown result type is preserved as is
+        assert_eq!(
+            lines[12],
+            LogLine::Event(LogEvent::Result {
+                id: 1,
+                result: ActivityResult::Other {
+                    type_: ResultType::Other(199),
+                    fields: vec![Field::String("foo".to_string())]
+                }
+            })
+        );
+        // Known activity type, but with missing fields
+        assert!(matches!(
+            &lines[13],
+            LogLine::Event(LogEvent::Start {
+                activity: Activity::Other {
+                    type_: ActivityType::Build,
+                    ..
+                },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(LogLine::parse(r#"@nix {"action":"start""#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_log_stream() {
+        let lines = log_stream(BUILD_LOG.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .await
+            .unwrap();
+        assert_eq!(lines, parse_all(BUILD_LOG));
+    }
+}
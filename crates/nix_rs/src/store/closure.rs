@@ -0,0 +1,260 @@
+//! Closures of store paths, and the difference between two of them
+//!
+//! Similar to `nix store diff-closures`, but structured.
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::command::{NixCmd, NixCmdError};
+
+use super::{path_info::PathInfo, StorePath};
+
+/// The closure of some store paths, with the NAR size of each path in it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Closure(pub BTreeMap<StorePath, u64>);
+
+impl Closure {
+    /// Compute the closure of the given (valid) paths with `nix path-info`
+    #[instrument(name = "closure")]
+    pub async fn from_nix(nix_cmd: &NixCmd, paths: &[StorePath]) -> Result<Closure, NixCmdError> {
+        let infos = PathInfo::from_nix(nix_cmd, paths, true).await?;
+        Ok(Closure(
+            infos
+                .into_iter()
+                .filter_map(|(path, info)| Some((path, info?.nar_size)))
+                .collect(),
+        ))
+    }
+
+    /// Total size of the closure, in bytes
+    pub fn size(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    /// The packages in the closure, keyed by name, with the size of each of
+    /// their versions
+    ///
+    /// Paths are grouped the way `nix store diff-closures` does: `glibc-2.39-5`
+    /// and `glibc-2.39-5-bin` are both version `2.39-5` of `glibc`.
+    pub fn packages(&self) -> BTreeMap<String, BTreeMap<String, u64>> {
+        let mut packages: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+        for (path, size) in &self.0 {
+            let Some(name) = path.name() else {
+                continue;
+            };
+            if path.is_drv() {
+                continue;
+            }
+            let (name, version) = split_name_version(name);
+            *packages
+                .entry(name.to_string())
+                .or_default()
+                .entry(version.to_string())
+                .or_default() += size;
+        }
+        packages
+    }
+}
+
+/// Split a store path name into the package name and version, ignoring the
+/// output name (eg: `bin` in `glibc-2.39-5-bin`)
+///
+/// As in `nix store diff-closures`, a trailing `-<lowercase letters>` (or
+/// `-lib32`, `-lib64`) is taken to be the output name, and the version starts
+/// at the first `-` followed by a non-letter, as in Nix's `DrvName`.
+fn split_name_version(name: &str) -> (&str, &str) {
+    let name = strip_output_name(name);
+    name.char_indices()
+        .find(|(i, c)| {
+            *c == '-'
+                && name[i + 1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| !c.is_ascii_alphabetic())
+        })
+        .map_or((name, ""), |(i, _)| (&name[..i], &name[i + 1..]))
+}
+
+/// Remove the output name suffix from a store path name, matching the regex
+/// `(.*)-([a-z]+|lib32|lib64)` of `nix store diff-closures`
+///
+/// This is ambiguous: a version suffix like `-unstable` is stripped too.
+fn strip_output_name(name: &str) -> &str {
+    match name.rsplit_once('-') {
+        Some((rest, output))
+            if !rest.is_empty()
+                && (matches!(output, "lib32" | "lib64")
+                    || (!output.is_empty() && output.chars().all(|c| c.is_ascii_lowercase()))) =>
+        {
+            rest
+        }
+        _ => name,
+    }
+}
+
+/// The difference between two [Closure]s
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClosureDiff {
+    /// Size of the old closure, in bytes
+    pub old_size: u64,
+    /// Size of the new closure, in bytes
+    pub new_size: u64,
+    /// Packages only in the new closure
+    pub added: Vec<PackageChange>,
+    /// Packages only in the old closure
+    pub removed: Vec<PackageChange>,
+    /// Packages in both closures, whose versions or size changed
+    pub changed: Vec<PackageChange>,
+}
+
+/// How a package differs between two [Closure]s
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub name: String,
+    /// Versions in the old closure (an empty version means unversioned)
+    pub old_versions: BTreeSet<String>,
+    /// Versions in the new closure
+    pub new_versions: BTreeSet<String>,
+    /// Change in size, in bytes
+    pub size_delta: i64,
+}
+
+impl ClosureDiff {
+    /// Compare the `old` closure to the `new` one
+    pub fn new(old: &Closure, new: &Closure) -> ClosureDiff {
+        let old_packages = old.packages();
+        let new_packages = new.packages();
+        let names: BTreeSet<&String> = old_packages.keys().chain(new_packages.keys()).collect();
+        let mut diff = ClosureDiff {
+            old_size: old.size(),
+            new_size: new.size(),
+            added: vec![],
+            removed: vec![],
+            changed: vec![],
+        };
+        for name in names {
+            let old_versions = old_packages.get(name);
+            let new_versions = new_packages.get(name);
+            let size = |versions: Option<&BTreeMap<String, u64>>| -> i64 {
+                versions.map_or(0, |v| v.values().sum::<u64>() as i64)
+            };
+            let versions = |versions: Option<&BTreeMap<String, u64>>| -> BTreeSet<String> {
+                versions.map_or_else(BTreeSet::new, |v| v.keys().cloned().collect())
+            };
+            let change = PackageChange {
+                name: name.clone(),
+                old_versions: versions(old_versions),
+                new_versions: versions(new_versions),
+                size_delta: size(new_versions) - size(old_versions),
+            };
+            match (old_versions, new_versions) {
+                (None, Some(_)) => diff.added.push(change),
+                (Some(_), None) => diff.removed.push(change),
+                _ if change.old_versions != change.new_versions || change.size_delta != 0 => {
+                    diff.changed.push(change)
+                }
+                _ => {}
+            }
+        }
+        diff
+    }
+
+    /// Change in the total closure size, in bytes
+    pub fn size_delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+
+    /// Whether the closures contain the same packages, of the same size
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn closure(paths: &[(&str, u64)]) -> Closure {
+        Closure(
+            paths
+                .iter()
+                .map(|(p, size)| {
+                    (
+                        StorePath::new(PathBuf::from(format!("/nix/store/{}", p))),
+                        *size,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_split_name_version() {
+        assert_eq!(split_name_version("hello-2.12.1"), ("hello", "2.12.1"));
+        assert_eq!(split_name_version("glibc-2.39-5-bin"), ("glibc", "2.39-5"));
+        assert_eq!(split_name_version("gcc-13.2.0-lib64"), ("gcc", "13.2.0"));
+        assert_eq!(split_name_version("font-5x7"), ("font", "5x7"));
+        assert_eq!(split_name_version("hello_world"), ("hello_world", ""));
+        // Like Nix, any trailing lowercase word is taken to be an output name
+        assert_eq!(split_name_version("source-foo"), ("source", ""));
+        assert_eq!(split_name_version("unstable"), ("unstable", ""));
+    }
+
+    #[test]
+    fn test_closure_diff() {
+        let old = closure(&[
+            ("9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1", 100),
+            ("ddwyrxif62r8n6xclvskjyy6szdhvj60-glibc-2.39-5", 1000),
+            ("55555555555555555555555555555555-glibc-2.39-5-bin", 50),
+            ("00000000000000000000000000000000-openssl-3.0.13", 500),
+            ("11111111111111111111111111111111-tzdata-2024a", 10),
+        ]);
+        let new = closure(&[
+            ("2bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1", 120),
+            ("ddwyrxif62r8n6xclvskjyy6szdhvj60-glibc-2.39-5", 1000),
+            ("55555555555555555555555555555555-glibc-2.39-5-bin", 50),
+            ("33333333333333333333333333333333-openssl-3.3.1", 600),
+            ("44444444444444444444444444444444-curl-8.7.1", 300),
+            ("11111111111111111111111111111111-tzdata-2024a", 10),
+        ]);
+        let diff = ClosureDiff::new(&old, &new);
+        assert_eq!(diff.size_delta(), 420);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].name, "curl");
+        assert!(diff.removed.is_empty());
+        let changed: Vec<(&str, i64)> = diff
+            .changed
+            .iter()
+            .map(|c| (c.name.as_str(), c.size_delta))
+            .collect();
+        assert_eq!(changed, vec![("hello", 20), ("openssl", 100)]);
+        assert_eq!(
+            diff.changed[1].old_versions,
+            BTreeSet::from(["3.0.13".to_string()])
+        );
+        assert!(ClosureDiff::new(&old, &old).is_empty());
+
+        // The outputs of a package are versions of the same package
+        let old = closure(&[
+            ("ddwyrxif62r8n6xclvskjyy6szdhvj60-glibc-2.39-5", 1000),
+            ("55555555555555555555555555555555-glibc-2.39-5-bin", 50),
+        ]);
+        let new = closure(&[
+            ("66666666666666666666666666666666-glibc-2.40", 1100),
+            ("77777777777777777777777777777777-glibc-2.40-bin", 60),
+        ]);
+        let diff = ClosureDiff::new(&old, &new);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(
+            diff.changed[0].old_versions,
+            BTreeSet::from(["2.39-5".to_string()])
+        );
+        assert_eq!(
+            diff.changed[0].new_versions,
+            BTreeSet::from(["2.40".to_string()])
+        );
+    }
+}
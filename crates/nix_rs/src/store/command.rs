@@ -0,0 +1,339 @@
+//! Rust wrapper for `nix-store`
+use std::{collections::BTreeSet, path::PathBuf, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{process::Command, sync::Semaphore, task::JoinSet};
+
+use crate::command::{
+    runner::{CommandRunner, Runner},
+    CommandError, NixCmdError,
+};
+
+use super::{DrvOut, StorePath};
+
+/// Maximum number of `nix-store` processes run at once by
+/// [NixStoreCmd::fetch_all_deps]
+pub const MAX_CONCURRENT_QUERIES: usize = 8;
+
+/// Maximum number of paths passed to a single `nix-store --query`
+pub const QUERY_BATCH_SIZE: usize = 256;
+
+/// The `nix-store` command
+/// See documentation for [nix-store](https://nixos.org/manual/nix/stable/command-ref/nix-store.html)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct NixStoreCmd {
+    /// How to execute the command (see [crate::command::runner])
+    #[serde(skip)]
+    pub runner: Runner,
+}
+
+impl NixStoreCmd {
+    /// Execute queries using the given [CommandRunner]
+    ///
+    /// The [Command] returned by [NixStoreCmd::command] is always a real
+    /// process.
+    pub fn with_runner(&mut self, runner: impl CommandRunner + 'static) {
+        self.runner = Runner::new(runner);
+    }
+
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new("nix-store");
+        cmd.kill_on_drop(true);
+        cmd
+    }
+}
+
+impl NixStoreCmd {
+    /// Fetch all build and runtime dependencies of given [DrvOut]s
+    ///
+    /// This is done by querying the deriver of each output path from [DrvOut]
+    /// using [NixStoreCmd::nix_store_query_deriver] (up to
+    /// [MAX_CONCURRENT_QUERIES] at a time), and then querying all
+    /// dependencies of the derivers in batches of [QUERY_BATCH_SIZE] paths.
+    /// The deduplicated dependencies are returned in sorted order.
+    ///
+    /// Output paths without a known deriver (eg: substituted paths whose
+    /// derivation was garbage collected) contribute their runtime
+    /// dependencies only.
+    pub async fn fetch_all_deps(
+        &self,
+        out_paths: Vec<DrvOut>,
+    ) -> Result<Vec<StorePath>, NixStoreCmdError> {
+        let out_paths: BTreeSet<DrvOut> = out_paths.into_iter().collect();
+        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_QUERIES));
+        let mut queries = JoinSet::new();
+        for out in out_paths {
+            let cmd = self.clone();
+            let semaphore = semaphore.clone();
+            queries.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let deriver = cmd.nix_store_query_deriver(out.0.clone()).await;
+                (out, deriver)
+            });
+        }
+
+        let mut drvs = BTreeSet::new();
+        let mut underived = BTreeSet::new();
+        while let Some(result) = queries.join_next().await {
+            let (out, deriver) = result.map_err(std::io::Error::other)?;
+            match deriver {
+                Ok(drv) => {
+                    drvs.insert(drv.0);
+                }
+                Err(NixStoreCmdError::UnknownDeriver) => {
+                    tracing::warn!(
+                        "Unknown deriver of {}; only its runtime dependencies are included",
+                        out.0.display()
+                    );
+                    underived.insert(out.0);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut all_outs = BTreeSet::new();
+        all_outs.extend(self.query_requisites_batched(&drvs, true).await?);
+        all_outs.extend(self.query_requisites_batched(&underived, false).await?);
+        Ok(all_outs.into_iter().collect())
+    }
+
+    /// Query the requisites of the given paths, [QUERY_BATCH_SIZE] paths per
+    /// `nix-store` invocation
+    ///
+    /// The requisites of all the paths are returned together, as `nix-store`
+    /// does not tell which path requires what.
+    pub async fn query_requisites_batched(
+        &self,
+        paths: &BTreeSet<PathBuf>,
+        include_outputs: bool,
+    ) -> Result<Vec<StorePath>, NixStoreCmdError> {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        let mut all = vec![];
+        for batch in paths.chunks(QUERY_BATCH_SIZE) {
+            let mut args = vec!["--requisites"];
+            if include_outputs {
+                args.push("--include-outputs");
+            }
+            args.extend(batch.iter().map(|s| s.as_str()));
+            all.extend(parse_store_paths(&self.run_query(&args).await?));
+        }
+        Ok(all)
+    }
+
+    /// Return the derivation used to build the given build output.
+    pub async fn nix_store_query_deriver(
+        &self,
+        out_path: PathBuf,
+    ) -> Result<DrvOut, NixStoreCmdError> {
+        let out = self
+            .run_query(&["--valid-derivers", out_path.to_string_lossy().as_ref()])
+            .await?;
+        // A path may have several valid derivers; any of them will do
+        let drv_path = out.lines().next().unwrap_or_default().trim().to_string();
+        if drv_path.is_empty() || drv_path == "unknown-deriver" {
+            return Err(NixStoreCmdError::UnknownDeriver);
+        }
+        Ok(DrvOut(PathBuf::from(drv_path)))
+    }
+
+    /// Given a [StorePath::Drv], this function recursively queries and return all
+    /// of its dependencies in the Nix store.
+    pub async fn nix_store_query_requisites_with_outputs(
+        &self,
+        drv_path: DrvOut,
+    ) -> Result<Vec<StorePath>, NixStoreCmdError> {
+        let out = self
+            .run_query(&[
+                "--requisites",
+                "--include-outputs",
+                drv_path.0.to_string_lossy().as_ref(),
+            ])
+            .await?;
+        Ok(parse_store_paths(&out))
+    }
+
+    /// Return the store paths that directly refer to the given path
+    pub async fn nix_store_query_referrers(
+        &self,
+        path: &StorePath,
+    ) -> Result<Vec<StorePath>, NixStoreCmdError> {
+        let out = self
+            .run_query(&["--referrers", path.as_path().to_string_lossy().as_ref()])
+            .await?;
+        Ok(parse_store_paths(&out))
+    }
+
+    /// Run `nix-store --query` with the given arguments, returning stdout
+    async fn run_query(&self, args: &[&str]) -> Result<String, NixStoreCmdError> {
+        let mut cmd = self.command();
+        cmd.arg("--query").args(args);
+        crate::command::trace_cmd(&cmd);
+        let out = self.runner.get().output(&mut cmd).await?;
+        if out.status.success() {
+            Ok(String::from_utf8(out.stdout)?)
+        } else {
+            let stderr = Some(String::from_utf8_lossy(&out.stderr).to_string());
+            let exit_code = out.status.code();
+            Err(CommandError::ProcessFailed { stderr, exit_code }.into())
+        }
+    }
+}
+
+/// Parse newline separated store paths
+fn parse_store_paths(out: &str) -> Vec<StorePath> {
+    out.lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .map(PathBuf::from)
+        .map(StorePath::new)
+        .collect()
+}
+
+/// `nix-store` command errors
+#[derive(Error, Debug)]
+pub enum NixStoreCmdError {
+    #[error(transparent)]
+    NixCmdError(#[from] NixCmdError),
+
+    #[error("Unknown deriver")]
+    UnknownDeriver,
+}
+
+impl From<std::io::Error> for NixStoreCmdError {
+    fn from(err: std::io::Error) -> Self {
+        let cmd_error: CommandError = err.into();
+        cmd_error.into()
+    }
+}
+
+impl From<std::string::FromUtf8Error> for NixStoreCmdError {
+    fn from(err: std::string::FromUtf8Error) -> Self {
+        let cmd_error: CommandError = err.into();
+        cmd_error.into()
+    }
+}
+
+impl From<CommandError> for NixStoreCmdError {
+    fn from(err: CommandError) -> Self {
+        let nixcmd_error: NixCmdError = err.into();
+        nixcmd_error.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::runner::{Recording, ReplayRunner};
+
+    fn recording(args: &[&str], stdout: &str) -> Recording {
+        Recording {
+            args: [&["--query"], args]
+                .concat()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+        }
+    }
+
+    fn out(path: &str) -> DrvOut {
+        DrvOut(PathBuf::from(path))
+    }
+
+    #[tokio::test]
+    async fn test_fetch_all_deps_dedup() {
+        // Both outputs of `hello` share its deriver, which is queried once
+        let drv = "/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv";
+        let mut cmd = NixStoreCmd::default();
+        cmd.with_runner(ReplayRunner::new(vec![
+            recording(&["--valid-derivers", "/nix/store/hello"], drv),
+            recording(&["--valid-derivers", "/nix/store/hello-man"], drv),
+            recording(
+                &["--requisites", "--include-outputs", drv],
+                "/nix/store/glibc\n/nix/store/hello\n/nix/store/hello-man\n/nix/store/glibc\n",
+            ),
+        ]));
+        let deps = cmd
+            .fetch_all_deps(vec![
+                out("/nix/store/hello"),
+                out("/nix/store/hello-man"),
+                out("/nix/store/hello"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            deps,
+            [
+                "/nix/store/glibc",
+                "/nix/store/hello",
+                "/nix/store/hello-man"
+            ]
+            .map(|p| StorePath::new(PathBuf::from(p)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_all_deps_unknown_deriver() {
+        // The derivation of a substituted path may be garbage collected
+        let mut cmd = NixStoreCmd::default();
+        cmd.with_runner(ReplayRunner::new(vec![
+            recording(&["--valid-derivers", "/nix/store/hello"], ""),
+            recording(
+                &["--requisites", "/nix/store/hello"],
+                "/nix/store/glibc\n/nix/store/hello\n",
+            ),
+        ]));
+        let deps = cmd
+            .fetch_all_deps(vec![out("/nix/store/hello")])
+            .await
+            .unwrap();
+        assert_eq!(
+            deps,
+            ["/nix/store/glibc", "/nix/store/hello"].map(|p| StorePath::new(PathBuf::from(p)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_all_deps_batches() {
+        let n = QUERY_BATCH_SIZE + 1;
+        let outs: Vec<String> = (0..n).map(|i| format!("/nix/store/out-{:03}", i)).collect();
+        let drvs: Vec<String> = (0..n)
+            .map(|i| format!("/nix/store/out-{:03}.drv", i))
+            .collect();
+        let mut recordings: Vec<Recording> = outs
+            .iter()
+            .zip(&drvs)
+            .map(|(out, drv)| recording(&["--valid-derivers", out], drv))
+            .collect();
+        for batch in drvs.chunks(QUERY_BATCH_SIZE) {
+            let mut args = vec!["--requisites", "--include-outputs"];
+            args.extend(batch.iter().map(String::as_str));
+            // Each derivation requires only its output
+            let stdout = batch
+                .iter()
+                .map(|drv| drv.trim_end_matches(".drv"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            recordings.push(recording(&args, &stdout));
+        }
+        assert_eq!(recordings.len(), n + 2);
+
+        let mut cmd = NixStoreCmd::default();
+        cmd.with_runner(ReplayRunner::new(recordings));
+        let deps = cmd
+            .fetch_all_deps(outs.iter().map(|p| out(p)).collect())
+            .await
+            .unwrap();
+        assert_eq!(deps.len(), n);
+        assert_eq!(
+            deps.last(),
+            Some(&StorePath::new(PathBuf::from(&outs[n - 1])))
+        );
+    }
+}
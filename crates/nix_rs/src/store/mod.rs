@@ -0,0 +1,237 @@
+//! Rust module for the Nix store
+//!
+//! See <https://nix.dev/manual/nix/stable/store/store-path>
+pub mod closure;
+pub mod command;
+pub mod path_info;
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The store directory used when `$NIX_STORE_DIR` is not set
+pub const DEFAULT_STORE_DIR: &str = "/nix/store";
+
+/// Characters of the base-32 encoding used by Nix for the hash part
+const BASE32_CHARS: &str = "0123456789abcdfghijklmnpqrsvwxyz";
+
+/// Length of the hash part of a store path
+const HASH_PART_LEN: usize = 32;
+
+/// The Nix store directory (`/nix/store` unless `$NIX_STORE_DIR` is set)
+pub fn store_dir() -> PathBuf {
+    std::env::var("NIX_STORE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_STORE_DIR))
+}
+
+/// Nix derivation output path
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
+pub struct DrvOut(pub PathBuf);
+
+impl DrvOut {
+    pub fn as_store_path(self) -> StorePath {
+        StorePath::Other(self.0)
+    }
+}
+
+/// Represents a path in the Nix store, see: <https://zero-to-nix.com/concepts/nix-store#store-paths>
+///
+/// [StorePath::new] accepts any path (including paths inside a store path);
+/// use [StorePath::parse_in] or [FromStr] to validate it.
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
+#[serde(from = "PathBuf", into = "PathBuf")]
+pub enum StorePath {
+    /// Derivation path (ends with `.drv`).
+    Drv(PathBuf),
+    /// Other paths in the Nix store, such as build outputs.
+    /// This won't be a derivation path.
+    Other(PathBuf),
+}
+
+/// Errors when parsing a [StorePath]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StorePathError {
+    #[error("'{}' is not a direct child of the store directory {}", path.display(), store_dir.display())]
+    NotInStore { path: PathBuf, store_dir: PathBuf },
+
+    #[error("Invalid hash part in store path '{}'", .0.display())]
+    InvalidHash(PathBuf),
+
+    #[error("Invalid name in store path '{}'", .0.display())]
+    InvalidName(PathBuf),
+}
+
+impl StorePath {
+    pub fn new(path: PathBuf) -> Self {
+        if path.extension().is_some_and(|ext| ext == "drv") {
+            StorePath::Drv(path)
+        } else {
+            StorePath::Other(path)
+        }
+    }
+
+    /// Parse a store path, checking that it is directly in `store_dir` and
+    /// that its base name is a valid `<hash>-<name>`
+    pub fn parse_in(store_dir: &Path, path: &Path) -> Result<Self, StorePathError> {
+        let not_in_store = || StorePathError::NotInStore {
+            path: path.to_path_buf(),
+            store_dir: store_dir.to_path_buf(),
+        };
+        if path.parent() != Some(store_dir) {
+            return Err(not_in_store());
+        }
+        let base_name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .ok_or_else(not_in_store)?;
+        let (hash, name) = base_name
+            .split_once('-')
+            .ok_or_else(|| StorePathError::InvalidHash(path.to_path_buf()))?;
+        if !is_hash_part(hash) {
+            return Err(StorePathError::InvalidHash(path.to_path_buf()));
+        }
+        let valid_name_char = |c: char| c.is_ascii_alphanumeric() || "+-._?=".contains(c);
+        if name.is_empty()
+            || name.len() > 211
+            || name.starts_with('.')
+            || !name.chars().all(valid_name_char)
+        {
+            return Err(StorePathError::InvalidName(path.to_path_buf()));
+        }
+        Ok(StorePath::new(path.to_path_buf()))
+    }
+
+    pub fn as_path(&self) -> &PathBuf {
+        match self {
+            StorePath::Drv(p) => p,
+            StorePath::Other(p) => p,
+        }
+    }
+
+    /// Whether this is a derivation
+    pub fn is_drv(&self) -> bool {
+        matches!(self, StorePath::Drv(_))
+    }
+
+    /// The `<hash>-<name>` component of the path (eg:
+    /// `9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1`)
+    ///
+    /// For paths inside a store path, this is the first component with a
+    /// valid hash part, whatever the store directory.
+    pub fn base_name(&self) -> Option<&str> {
+        self.as_path()
+            .components()
+            .filter_map(|c| match c {
+                std::path::Component::Normal(s) => s.to_str(),
+                _ => None,
+            })
+            .find(|s| {
+                s.split_once('-')
+                    .is_some_and(|(hash, _)| is_hash_part(hash))
+            })
+    }
+
+    /// The hash part of the path (eg: `9bwryidal9q3g91cjm6xschfn4ikd82q`)
+    pub fn hash_part(&self) -> Option<&str> {
+        self.base_name()?.split_once('-').map(|(hash, _)| hash)
+    }
+
+    /// The name part of the path (eg: `hello-2.12.1`, or `hello-2.12.1.drv`
+    /// for derivations)
+    pub fn name(&self) -> Option<&str> {
+        self.base_name()?.split_once('-').map(|(_, name)| name)
+    }
+}
+
+/// Whether the given string is a valid hash part of a store path
+fn is_hash_part(s: &str) -> bool {
+    s.len() == HASH_PART_LEN && s.chars().all(|c| BASE32_CHARS.contains(c))
+}
+
+impl FromStr for StorePath {
+    type Err = StorePathError;
+
+    /// Parse a store path in [store_dir]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StorePath::parse_in(&store_dir(), Path::new(s.trim()))
+    }
+}
+
+impl From<PathBuf> for StorePath {
+    fn from(path: PathBuf) -> Self {
+        StorePath::new(path)
+    }
+}
+
+impl From<StorePath> for PathBuf {
+    fn from(path: StorePath) -> Self {
+        match path {
+            StorePath::Drv(p) => p,
+            StorePath::Other(p) => p,
+        }
+    }
+}
+
+impl fmt::Display for StorePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_path().display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_path() {
+        let store_dir = Path::new("/nix/store");
+        let drv = StorePath::parse_in(
+            store_dir,
+            Path::new("/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv"),
+        )
+        .unwrap();
+        assert!(drv.is_drv());
+        assert_eq!(drv.hash_part(), Some("g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy"));
+        assert_eq!(drv.name(), Some("hello-2.12.1.drv"));
+
+        let out = StorePath::new(PathBuf::from(
+            "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1/bin/hello",
+        ));
+        assert!(!out.is_drv());
+        assert_eq!(out.name(), Some("hello-2.12.1"));
+
+        // The store directory need not be the default one
+        let custom = StorePath::parse_in(
+            Path::new("/custom/store"),
+            Path::new("/custom/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1"),
+        )
+        .unwrap();
+        assert_eq!(custom.hash_part(), Some("9bwryidal9q3g91cjm6xschfn4ikd82q"));
+        assert_eq!(custom.name(), Some("hello-2.12.1"));
+
+        let invalid = |path: &str| StorePath::parse_in(store_dir, Path::new(path)).unwrap_err();
+        assert!(matches!(
+            invalid("/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1/bin/hello"),
+            StorePathError::NotInStore { .. }
+        ));
+        assert!(matches!(
+            invalid("/tmp/9bwryidal9q3g91cjm6xschfn4ikd82q-hello"),
+            StorePathError::NotInStore { .. }
+        ));
+        // `e` is not in the base-32 alphabet of Nix
+        assert!(matches!(
+            invalid("/nix/store/ebwryidal9q3g91cjm6xschfn4ikd82q-hello"),
+            StorePathError::InvalidHash(_)
+        ));
+        assert!(matches!(
+            invalid("/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-.hello"),
+            StorePathError::InvalidName(_)
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+//! Rust module for `nix path-info`
+use std::{collections::BTreeMap, path::PathBuf};
+
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::command::{NixCmd, NixCmdError};
+
+use super::StorePath;
+
+/// Metadata of a valid store path, as reported by `nix path-info --json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathInfo {
+    /// Hash of the NAR serialisation of the path (eg: `sha256:...`)
+    #[serde(default)]
+    pub nar_hash: Option<String>,
+    /// Size of the NAR serialisation of the path, in bytes
+    #[serde(default)]
+    pub nar_size: u64,
+    /// Size of the closure of the path, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closure_size: Option<u64>,
+    /// Store paths this path refers to
+    #[serde(default)]
+    pub references: Vec<StorePath>,
+    /// The derivation that produced this path, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deriver: Option<StorePath>,
+}
+
+/// `nix path-info --json` output, which changed format in Nix 2.19
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PathInfoJson {
+    /// Object keyed by path, with `null` for invalid paths
+    Map(BTreeMap<PathBuf, Option<PathInfo>>),
+    /// List of objects with a `path` (and `valid`) field
+    List(Vec<LegacyPathInfo>),
+}
+
+#[derive(Deserialize)]
+struct LegacyPathInfo {
+    path: PathBuf,
+    #[serde(default = "default_valid")]
+    valid: bool,
+    #[serde(flatten)]
+    info: PathInfo,
+}
+
+fn default_valid() -> bool {
+    true
+}
+
+impl From<PathInfoJson> for BTreeMap<StorePath, Option<PathInfo>> {
+    fn from(json: PathInfoJson) -> Self {
+        match json {
+            PathInfoJson::Map(m) => m
+                .into_iter()
+                .map(|(path, info)| (StorePath::new(path), info))
+                .collect(),
+            PathInfoJson::List(l) => l
+                .into_iter()
+                .map(|v| (StorePath::new(v.path), Some(v.info).filter(|_| v.valid)))
+                .collect(),
+        }
+    }
+}
+
+impl PathInfo {
+    /// Run `nix path-info --json --closure-size` on the given paths
+    ///
+    /// Invalid paths (not in the store) map to `None`. If `recursive` is
+    /// set, the result covers the closure of the given paths.
+    #[instrument(name = "path-info")]
+    pub async fn from_nix(
+        nix_cmd: &NixCmd,
+        paths: &[StorePath],
+        recursive: bool,
+    ) -> Result<BTreeMap<StorePath, Option<PathInfo>>, NixCmdError> {
+        let paths: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+        let mut args = vec!["path-info", "--json", "--closure-size"];
+        if recursive {
+            args.push("--recursive");
+        }
+        args.extend(paths.iter().map(|s| s.as_str()));
+        let v: PathInfoJson = nix_cmd.run_with_args_expecting_json(&args).await?;
+        Ok(v.into())
+    }
+
+    /// Whether the given path is valid (ie. present in the store)
+    pub async fn is_valid(nix_cmd: &NixCmd, path: &StorePath) -> Result<bool, NixCmdError> {
+        let infos = PathInfo::from_nix(nix_cmd, std::slice::from_ref(path), false).await?;
+        Ok(infos.into_values().any(|info| info.is_some()))
+    }
+
+    /// Size of the closure, if it was computed
+    pub fn closure_size(&self) -> Option<ByteSize> {
+        self.closure_size.map(ByteSize::b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::runner::{Recording, ReplayRunner};
+
+    const HELLO: &str = "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1";
+    const MISSING: &str = "/nix/store/00000000000000000000000000000000-missing";
+
+    fn cmd_replaying(stdout: &str) -> NixCmd {
+        let mut cmd = NixCmd::default();
+        cmd.with_runner(ReplayRunner::new(vec![Recording {
+            args: ["path-info", "--json", "--closure-size", HELLO, MISSING]
+                .map(String::from)
+                .to_vec(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+        }]));
+        cmd
+    }
+
+    async fn check(cmd: NixCmd) {
+        let paths = [HELLO, MISSING].map(|p| StorePath::new(PathBuf::from(p)));
+        let infos = PathInfo::from_nix(&cmd, &paths, false).await.unwrap();
+        let hello = infos[&paths[0]].as_ref().unwrap();
+        assert_eq!(hello.nar_size, 268912);
+        assert_eq!(hello.closure_size(), Some(ByteSize::b(32409120)));
+        assert_eq!(hello.references.len(), 2);
+        assert!(hello.deriver.as_ref().unwrap().is_drv());
+        assert_eq!(infos[&paths[1]], None);
+    }
+
+    #[tokio::test]
+    async fn test_path_info() {
+        // Nix >= 2.19
+        check(cmd_replaying(r#"{
+            "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1": {"narHash":"sha256-8PGNCRsS3GT3jdRkv4XUbhGLr8GVsBgwfMBRvoeRDxU=","narSize":268912,"closureSize":32409120,"references":["/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1","/nix/store/ddwyrxif62r8n6xclvskjyy6szdhvj60-glibc-2.39-5"],"deriver":"/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv"},
+            "/nix/store/00000000000000000000000000000000-missing": null
+        }"#)).await;
+        // Older Nix
+        check(cmd_replaying(r#"[
+            {"path":"/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1","narHash":"sha256:1ccmfc4ljsb2vjrk3awamp6r5qbdzs2ahpq3pl2ayp8qhlx4ghsi","narSize":268912,"closureSize":32409120,"references":["/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1","/nix/store/ddwyrxif62r8n6xclvskjyy6szdhvj60-glibc-2.39-5"],"deriver":"/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv"},
+            {"path":"/nix/store/00000000000000000000000000000000-missing","valid":false}
+        ]"#)).await;
+    }
+}
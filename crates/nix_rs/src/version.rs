@@ -1,7 +1,8 @@
 //! Rust module for `nix --version`
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
-use std::{fmt, str::FromStr};
+use std::{cmp::Ordering, fmt, str::FromStr};
 use thiserror::Error;
 
 use tracing::instrument;
@@ -9,11 +10,47 @@ use tracing::instrument;
 use crate::command::NixCmd;
 
 /// Nix version as parsed from `nix --version`
-#[derive(Clone, Copy, PartialOrd, PartialEq, Eq, Debug, SerializeDisplay, DeserializeFromStr)]
+///
+/// Versions are ordered by their version numbers, with pre-releases ordered
+/// before the corresponding release.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, SerializeDisplay, DeserializeFromStr)]
 pub struct NixVersion {
+    /// The implementation of Nix
+    pub implementation: NixImpl,
     pub major: u32,
     pub minor: u32,
     pub patch: u32,
+    /// The pre-release suffix following the version numbers, if any (eg:
+    /// `pre20240101_abcdef` in `2.24.0pre20240101_abcdef`)
+    pub pre: Option<String>,
+    /// The version of the implementation, when it is versioned separately
+    /// from the Nix it is based on (eg: `3.0.0` in `Determinate Nix 3.0.0`)
+    pub impl_version: Option<String>,
+}
+
+/// An implementation of Nix
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize, Default,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum NixImpl {
+    /// The reference implementation, <https://github.com/NixOS/nix>
+    #[default]
+    CppNix,
+    /// <https://lix.systems>
+    Lix,
+    /// Determinate Nix, a distribution of Nix by Determinate Systems
+    Determinate,
+}
+
+impl fmt::Display for NixImpl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NixImpl::CppNix => write!(f, "Nix"),
+            NixImpl::Lix => write!(f, "Lix"),
+            NixImpl::Determinate => write!(f, "Determinate Nix"),
+        }
+    }
 }
 
 #[derive(Error, Debug, Clone, PartialEq)]
@@ -30,66 +67,203 @@ impl FromStr for NixVersion {
     type Err = BadNixVersion;
 
     /// Parse the string output of `nix --version` into a [NixVersion]
+    ///
+    /// For example,
+    /// - `nix (Nix) 2.24.0pre20240101_abcdef`
+    /// - `nix (Lix, like Nix) 2.90.0`
+    /// - `nix (Determinate Nix 3.0.0) 2.26.3`
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         // NOTE: The parser is lenient in allowing pure nix version (produced
         // by [Display] instance), so as to work with serde_with instances.
-        let re = Regex::new(r"(?:nix \(Nix\) )?(\d+)\.(\d+)\.(\d+)")?;
+        let re = Regex::new(
+            r"^(?:nix \((?P<name>[^)]*)\) )?(?P<prefix>Lix |Determinate Nix (?:(?P<impl_version>\S+) \(Nix )?)?(\d+)\.(\d+)(?:\.(\d+))?([^\s)]*)",
+        )?;
 
-        let captures = re.captures(s).ok_or(BadNixVersion::Command)?;
-        let major = captures[1].parse::<u32>()?;
-        let minor = captures[2].parse::<u32>()?;
-        let patch = captures[3].parse::<u32>()?;
+        let captures = re.captures(s.trim()).ok_or(BadNixVersion::Command)?;
+        let name = captures
+            .name("name")
+            .or(captures.name("prefix"))
+            .map_or("", |m| m.as_str());
+        let implementation = if name.starts_with("Lix") {
+            NixImpl::Lix
+        } else if name.starts_with("Determinate") {
+            NixImpl::Determinate
+        } else {
+            NixImpl::CppNix
+        };
+        let major = captures[4].parse::<u32>()?;
+        let minor = captures[5].parse::<u32>()?;
+        let patch = match captures.get(6) {
+            Some(m) => m.as_str().parse::<u32>()?,
+            None => 0,
+        };
+        let pre = Some(captures[7].to_string()).filter(|s| !s.is_empty());
+        let impl_version = match implementation {
+            NixImpl::Determinate => captures
+                .name("impl_version")
+                .map(|m| m.as_str())
+                .or_else(|| name.strip_prefix("Determinate Nix "))
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()),
+            _ => None,
+        };
 
         Ok(NixVersion {
+            implementation,
             major,
             minor,
             patch,
+            pre,
+            impl_version,
         })
     }
 }
 
+impl Ord for NixVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                // A pre-release precedes the release
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+            .then_with(|| self.implementation.cmp(&other.implementation))
+            .then_with(|| self.impl_version.cmp(&other.impl_version))
+    }
+}
+
+impl PartialOrd for NixVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl NixVersion {
+    /// A release version of [NixImpl::CppNix]
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        NixVersion {
+            implementation: NixImpl::CppNix,
+            major,
+            minor,
+            patch,
+            pre: None,
+            impl_version: None,
+        }
+    }
+
+    /// Whether this is a pre-release version
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some()
+    }
+
     /// Get the output of `nix --version`
 
     #[instrument(name = "version")]
-    pub async fn from_nix() -> Result<NixVersion, super::command::NixCmdError> {
-        let v = NixCmd::default()
+    pub async fn from_nix(nix_cmd: &NixCmd) -> Result<NixVersion, super::command::NixCmdError> {
+        let v = nix_cmd
             .run_with_args_expecting_fromstr(&["--version"])
             .await?;
         Ok(v)
     }
 }
 /// The String view for [NixVersion]
+///
+/// The implementation is shown, unless it is [NixImpl::CppNix], along with
+/// its own version if it has one (eg: `Determinate Nix 3.0.0 (Nix 2.26.3)`).
 impl fmt::Display for NixVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        if self.implementation != NixImpl::CppNix {
+            write!(f, "{} ", self.implementation)?;
+        }
+        if let Some(impl_version) = &self.impl_version {
+            write!(f, "{} (Nix ", impl_version)?;
+        }
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "{}", pre)?;
+        }
+        if self.impl_version.is_some() {
+            write!(f, ")")?;
+        }
+        Ok(())
     }
 }
 
 #[tokio::test]
 async fn test_run_nix_version() {
-    let nix_version = NixVersion::from_nix().await.unwrap();
+    let nix_version = NixVersion::from_nix(&NixCmd::default()).await.unwrap();
     println!("Nix version: {}", nix_version);
 }
 
 #[tokio::test]
-async fn test_parse_nix_version() {
+async fn test_replay_nix_version() {
+    use crate::command::runner::{Recording, ReplayRunner};
+    let mut cmd = NixCmd::default();
+    cmd.with_runner(ReplayRunner::new(vec![Recording {
+        args: vec!["--version".to_string()],
+        stdout: "nix (Nix) 2.18.1\n".to_string(),
+        stderr: String::new(),
+        exit_code: 0,
+    }]));
+    assert_eq!(
+        NixVersion::from_nix(&cmd).await.unwrap(),
+        NixVersion::new(2, 18, 1)
+    );
+}
+
+#[test]
+fn test_parse_nix_version() {
     assert_eq!(
         NixVersion::from_str("nix (Nix) 2.13.0"),
-        Ok(NixVersion {
-            major: 2,
-            minor: 13,
-            patch: 0
-        })
+        Ok(NixVersion::new(2, 13, 0))
     );
 
     // Parse simple nix version
     assert_eq!(
         NixVersion::from_str("2.13.0"),
-        Ok(NixVersion {
-            major: 2,
-            minor: 13,
-            patch: 0
-        })
+        Ok(NixVersion::new(2, 13, 0))
+    );
+
+    // Old versions lack the patch number
+    assert_eq!(
+        NixVersion::from_str("nix (Nix) 2.3"),
+        Ok(NixVersion::new(2, 3, 0))
+    );
+
+    let pre = NixVersion::from_str("nix (Nix) 2.24.0pre20240101_abcdef").unwrap();
+    assert_eq!(pre.pre.as_deref(), Some("pre20240101_abcdef"));
+    assert!(pre.is_prerelease());
+
+    let lix = NixVersion::from_str("nix (Lix, like Nix) 2.90.0").unwrap();
+    assert_eq!(lix.implementation, NixImpl::Lix);
+    assert_eq!((lix.major, lix.minor, lix.patch), (2, 90, 0));
+
+    let determinate = NixVersion::from_str("nix (Determinate Nix 3.0.0) 2.26.3").unwrap();
+    assert_eq!(determinate.implementation, NixImpl::Determinate);
+    assert_eq!(
+        (determinate.major, determinate.minor, determinate.patch),
+        (2, 26, 3)
+    );
+    assert_eq!(determinate.impl_version.as_deref(), Some("3.0.0"));
+    assert_eq!(
+        determinate.to_string(),
+        "Determinate Nix 3.0.0 (Nix 2.26.3)"
     );
+
+    // Display round-trips
+    for v in [pre, lix, determinate] {
+        assert_eq!(NixVersion::from_str(&v.to_string()), Ok(v));
+    }
+}
+
+#[test]
+fn test_nix_version_ordering() {
+    let v = |s: &str| NixVersion::from_str(s).unwrap();
+    assert!(v("2.24.0pre20240101_abcdef") < v("2.24.0"));
+    assert!(v("2.24.0pre20240101_abcdef") > v("2.23.3"));
+    assert!(v("2.18.1") < v("2.18.10"));
+    assert!(v("Lix 2.90.0") > v("2.18.1"));
 }
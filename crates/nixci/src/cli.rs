@@ -6,16 +6,16 @@ use colored::Colorize;
 use nix_rs::{
     command::NixCmd,
     config::NixConfig,
-    flake::{system::System, url::FlakeUrl},
+    flake::{
+        system::System,
+        url::{FlakeUrl, FlakeUrlError},
+    },
 };
 
 use crate::{
     config,
     github::pull_request::{PullRequest, PullRequestRef},
-    nix::{
-        devour_flake,
-        system_list::{SystemsList, SystemsListFlakeRef},
-    },
+    nix::system_list::{SystemsList, SystemsListFlakeRef},
 };
 
 /// A reference to some flake living somewhere
@@ -32,7 +32,10 @@ impl FromStr for FlakeRef {
     fn from_str(s: &str) -> std::result::Result<FlakeRef, String> {
         let flake_ref = match PullRequestRef::from_web_url(s) {
             Some(pr) => FlakeRef::GithubPR(pr),
-            None => FlakeRef::Flake(FlakeUrl(s.to_string())),
+            None => {
+                let url: FlakeUrl = s.parse().map_err(|e: FlakeUrlError| e.to_string())?;
+                FlakeRef::Flake(url.to_forge_url().unwrap_or(url))
+            }
         };
         Ok(flake_ref)
     }
@@ -81,10 +84,6 @@ impl CliArgs {
     pub async fn preprocess(&mut self) -> anyhow::Result<()> {
         // Avoid using `--extra-experimental-features` if possible.
         self.nixcmd.with_flakes();
-        // Adjust to devour_flake's expectations
-        if let Command::Build(build_cfg) = &mut self.command {
-            devour_flake::transform_override_inputs(&mut build_cfg.extra_nix_build_args);
-        }
         Ok(())
     }
 }
@@ -120,7 +119,7 @@ impl Command {
     /// Get the nixci [config::Config] associated with this subcommand
     pub async fn get_config(cmd: &NixCmd, flake_ref: &FlakeRef) -> anyhow::Result<config::Config> {
         let url = flake_ref.to_flake_url().await?;
-        tracing::info!("{}", format!("🍏 Building {}", url.0).bold());
+        tracing::info!("{}", format!("🍏 Building {}", url).bold());
         let cfg = config::Config::from_flake_url(cmd, &url).await?;
         tracing::debug!("Config: {cfg:?}");
         Ok(cfg)
@@ -158,6 +157,21 @@ pub struct BuildConfig {
     /// useful to explicitly push all dependencies to a cache.
     #[clap(long, short = 'd')]
     pub print_all_dependencies: bool,
+
+    /// Discover and build outputs using devour-flake
+    ///
+    /// By default, the outputs are evaluated and built directly with `nix`.
+    /// This fallback mode requires omnix to be built with devour-flake.
+    #[arg(long)]
+    pub devour_flake: bool,
+
+    /// Keep going after a subflake fails to build
+    ///
+    /// The other outputs of the failing subflake are built too (see `nix
+    /// build --keep-going`). Either way, a summary of the outputs that failed
+    /// is printed at the end.
+    #[arg(long, short = 'k')]
+    pub keep_going: bool,
 }
 
 impl BuildConfig {
@@ -192,7 +206,7 @@ mod tests {
     fn test_current_dir() {
         assert_eq!(
             FlakeRef::from_str(".").unwrap(),
-            FlakeRef::Flake(FlakeUrl(".".to_string()))
+            FlakeRef::Flake(".".parse().unwrap())
         );
     }
 
@@ -200,7 +214,15 @@ mod tests {
     fn test_flake_url() {
         assert_eq!(
             FlakeRef::from_str("github:srid/nixci").unwrap(),
-            FlakeRef::Flake(FlakeUrl("github:srid/nixci".to_string()))
+            FlakeRef::Flake("github:srid/nixci".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_github_web_url() {
+        assert_eq!(
+            FlakeRef::from_str("https://github.com/srid/nixci").unwrap(),
+            FlakeRef::Flake("github:srid/nixci".parse().unwrap())
         );
     }
 }
@@ -10,7 +10,7 @@ use nix_rs::{
 };
 use serde::Deserialize;
 
-use crate::cli::BuildConfig;
+use crate::{cli::BuildConfig, nix::devour_flake::transform_override_inputs};
 
 /// The `nixci` configuration encoded in flake.nix
 ///
@@ -46,7 +46,7 @@ impl Config {
     ///
     /// Example:
     /// ```text
-    /// let url = FlakeUrl("github:srid/haskell-flake#default.dev".to_string());
+    /// let url: FlakeUrl = "github:srid/haskell-flake#default.dev".parse()?;
     /// let cfg = Config::from_flake_url(&url).await?;
     /// ```
     /// along with the config.
@@ -124,6 +124,14 @@ impl SubFlakish {
         }
     }
 
+    /// The `--override-input` arguments for evaluating this subflake
+    pub fn override_input_args(&self) -> Vec<String> {
+        self.override_inputs
+            .iter()
+            .flat_map(|(k, v)| ["--override-input".to_string(), k.clone(), v.to_string()])
+            .collect()
+    }
+
     /// Return the devour-flake `nix build` arguments for building all the outputs in this
     /// subflake configuration.
     pub fn nix_build_args_for_flake(
@@ -131,7 +139,10 @@ impl SubFlakish {
         build_cfg: &BuildConfig,
         flake_url: &FlakeUrl,
     ) -> Vec<String> {
-        std::iter::once(flake_url.sub_flake_url(self.dir.clone()).0)
+        let mut extra_nix_build_args = build_cfg.extra_nix_build_args.clone();
+        // Adjust to devour_flake's expectations
+        transform_override_inputs(&mut extra_nix_build_args);
+        std::iter::once(flake_url.sub_flake_url(self.dir.clone()).to_string())
             .chain(self.override_inputs.iter().flat_map(|(k, v)| {
                 [
                     "--override-input".to_string(),
@@ -139,15 +150,15 @@ impl SubFlakish {
                     // devour-flake uses that input name to refer to the user's
                     // flake.
                     format!("flake/{}", k),
-                    v.0.to_string(),
+                    v.to_string(),
                 ]
             }))
             .chain([
                 "--override-input".to_string(),
                 "systems".to_string(),
-                build_cfg.systems.0 .0.clone(),
+                build_cfg.systems.0.to_string(),
             ])
-            .chain(build_cfg.extra_nix_build_args.iter().cloned())
+            .chain(extra_nix_build_args)
             .collect()
     }
 }
@@ -160,10 +171,9 @@ mod tests {
     async fn test_config_loading() {
         // Testing this flake:
         // https://github.com/srid/haskell-flake/blob/76214cf8b0d77ed763d1f093ddce16febaf07365/flake.nix#L15-L67
-        let url = &FlakeUrl(
-            "github:srid/haskell-flake/76214cf8b0d77ed763d1f093ddce16febaf07365#default.dev"
-                .to_string(),
-        );
+        let url = &"github:srid/haskell-flake/76214cf8b0d77ed763d1f093ddce16febaf07365#default.dev"
+            .parse()
+            .unwrap();
         let cfg = Config::from_flake_url(&NixCmd::default(), url)
             .await
             .unwrap();
@@ -171,4 +181,40 @@ mod tests {
         assert_eq!(cfg.selected_subflake, Some("dev".to_string()));
         assert_eq!(cfg.subflakes.0.len(), 7);
     }
+
+    #[tokio::test]
+    async fn test_config_loading_offline() {
+        use nix_rs::command::runner::{Recording, ReplayRunner};
+        let eval = |attr: &str, stdout: &str, stderr: &str, exit_code| Recording {
+            args: vec![
+                "eval".to_string(),
+                format!("github:example/repo#{}", attr),
+                "--json".to_string(),
+            ],
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            exit_code,
+        };
+        let mut cmd = NixCmd::default();
+        cmd.with_runner(ReplayRunner::new(vec![
+            eval(
+                "om.ci.default",
+                "",
+                "error: flake 'github:example/repo' does not provide attribute 'om.ci.default'",
+                1,
+            ),
+            eval(
+                "nixci.default",
+                r#"{"root": {"dir": "."}, "dev": {"dir": "./dev", "overrideInputs": {"repo": "."}, "systems": ["x86_64-linux"]}}"#,
+                "",
+                0,
+            ),
+        ]));
+        let url = &"github:example/repo#default.dev".parse().unwrap();
+        let cfg = Config::from_flake_url(&cmd, url).await.unwrap();
+        assert_eq!(cfg.name, "default");
+        assert_eq!(cfg.selected_subflake, Some("dev".to_string()));
+        assert_eq!(cfg.subflakes.0.len(), 2);
+        assert_eq!(cfg.subflakes.0["dev"].dir, "./dev");
+    }
 }
@@ -1,6 +1,6 @@
 /// Enough types to get branch info from Pull Request URL
 use anyhow::{bail, Context};
-use nix_rs::flake::url::FlakeUrl;
+use nix_rs::flake::url::{kind::FlakeUrlKind, FlakeUrl};
 use reqwest::header::USER_AGENT;
 use serde::Deserialize;
 use try_guard::guard;
@@ -72,11 +72,17 @@ impl PullRequest {
         // We cannot use `github:user/repo` syntax, because it doesn't support
         // special characters in branch name. For that, we need to use the full
         // git+https URL with url encoded `ref` query parameter.
-        FlakeUrl(format!(
-            "git+https://github.com/{}?ref={}",
-            self.head.repo.full_name,
-            urlencoding::encode(&self.head.ref_)
-        ))
+        FlakeUrl {
+            kind: FlakeUrlKind::Git {
+                url: format!("https://github.com/{}", self.head.repo.full_name),
+                explicit: true,
+            },
+            params: vec![(
+                "ref".to_string(),
+                urlencoding::encode(&self.head.ref_).to_string(),
+            )],
+            attr: None,
+        }
     }
 }
 
@@ -2,6 +2,7 @@ pub mod cli;
 pub mod config;
 pub mod github;
 pub mod nix;
+pub mod summary;
 
 use anyhow::Context;
 use clap::CommandFactory;
@@ -11,12 +12,16 @@ use std::io;
 
 use cli::{BuildConfig, CliArgs, Command};
 use colored::Colorize;
-use nix::{
-    devour_flake::DevourFlakeOutput,
-    nix_store::{DrvOut, NixStoreCmd, StorePath},
-};
+use nix::flake_outputs::{OutputResult, OutputStatus};
 use nix_health::{traits::Checkable, NixHealth};
-use nix_rs::{command::NixCmd, config::NixConfig, flake::url::FlakeUrl, info::NixInfo};
+use nix_rs::{
+    command::NixCmd,
+    config::NixConfig,
+    flake::{system::System, url::FlakeUrl},
+    info::NixInfo,
+    store::{command::NixStoreCmd, DrvOut, StorePath},
+};
+use summary::{SubflakeResult, Summary};
 use tracing::instrument;
 
 /// Run nixci on the given [CliArgs], returning the built outputs in sorted order.
@@ -64,15 +69,16 @@ async fn nixci_build(
 ) -> anyhow::Result<Vec<StorePath>> {
     let mut all_outs = HashSet::new();
 
-    let all_devour_flake_outs = nixci_subflakes(cmd, verbose, build_cfg, cfg, nix_config).await?;
+    let summary = nixci_subflakes(cmd, verbose, build_cfg, cfg, nix_config).await?;
+    let all_built_outs: HashSet<DrvOut> = summary.outs().cloned().collect();
 
     if build_cfg.print_all_dependencies {
-        let all_deps = NixStoreCmd
-            .fetch_all_deps(all_devour_flake_outs.into_iter().collect())
+        let all_deps = NixStoreCmd::default()
+            .fetch_all_deps(all_built_outs.into_iter().collect())
             .await?;
         all_outs.extend(all_deps.into_iter());
     } else {
-        let store_paths: HashSet<StorePath> = all_devour_flake_outs
+        let store_paths: HashSet<StorePath> = all_built_outs
             .into_iter()
             .map(DrvOut::as_store_path)
             .collect();
@@ -83,6 +89,16 @@ async fn nixci_build(
         println!("{}", out);
     }
 
+    summary.print();
+    let (failures, skipped) = (summary.failures(), summary.skipped());
+    if failures > 0 || skipped > 0 {
+        anyhow::bail!(
+            "{} output(s) failed to build, {} skipped",
+            failures,
+            skipped
+        );
+    }
+
     Ok(all_outs.into_iter().collect())
 }
 
@@ -92,8 +108,8 @@ async fn nixci_subflakes(
     build_cfg: &BuildConfig,
     cfg: &config::Config,
     nix_config: &NixConfig,
-) -> anyhow::Result<HashSet<DrvOut>> {
-    let mut result = HashSet::new();
+) -> anyhow::Result<Summary> {
+    let mut summary = Summary::default();
     let systems = build_cfg.get_systems(cmd, nix_config).await?;
 
     for (subflake_name, subflake) in &cfg.subflakes.0 {
@@ -108,16 +124,25 @@ async fn nixci_subflakes(
         }
         tracing::info!("🍎 {}", name);
         if subflake.can_build_on(&systems) {
-            let outs = nixci_subflake(
+            let result = nixci_subflake(
                 cmd,
                 verbose,
                 build_cfg,
+                &systems,
                 &cfg.flake_url,
                 subflake_name,
                 subflake,
             )
-            .await?;
-            result.extend(outs.0);
+            .await;
+            let subflake_result = SubflakeResult {
+                name: format!("{}.{}", cfg.name, subflake_name),
+                result,
+            };
+            let failed = subflake_result.failed();
+            summary.0.push(subflake_result);
+            if failed && !build_cfg.keep_going {
+                break;
+            }
         } else {
             tracing::info!(
                 "🍊 {} {}",
@@ -127,25 +152,51 @@ async fn nixci_subflakes(
         }
     }
 
-    Ok(result)
+    Ok(summary)
 }
 
-#[instrument(skip(build_cfg, url))]
+#[instrument(skip(build_cfg, systems, url))]
 async fn nixci_subflake(
     cmd: &NixCmd,
     verbose: bool,
     build_cfg: &BuildConfig,
+    systems: &[System],
     url: &FlakeUrl,
     subflake_name: &str,
     subflake: &config::SubFlakish,
-) -> anyhow::Result<DevourFlakeOutput> {
+) -> anyhow::Result<Vec<OutputResult>> {
+    let subflake_url = url.sub_flake_url(subflake.dir.clone());
     if subflake.override_inputs.is_empty() {
-        nix::lock::nix_flake_lock_check(cmd, &url.sub_flake_url(subflake.dir.clone())).await?;
+        nix::lock::nix_flake_lock_check(cmd, &subflake_url).await?;
     }
 
-    let nix_args = subflake.nix_build_args_for_flake(build_cfg, url);
-    let outs = nix::devour_flake::devour_flake(cmd, verbose, nix_args).await?;
-    Ok(outs)
+    if build_cfg.devour_flake {
+        let mut devour_args = subflake.nix_build_args_for_flake(build_cfg, url);
+        if build_cfg.keep_going {
+            devour_args.push("--keep-going".to_string());
+        }
+        let outs = nix::devour_flake::devour_flake(cmd, verbose, devour_args).await?;
+        // devour-flake builds all outputs as one
+        return Ok(vec![OutputResult {
+            attr: "(all outputs)".to_string(),
+            status: OutputStatus::Built,
+            outs: outs.0.into_iter().collect(),
+        }]);
+    }
+
+    let mut nix_args = build_cfg.extra_nix_build_args.clone();
+    if build_cfg.keep_going {
+        nix_args.push("--keep-going".to_string());
+    }
+    let eval_args: Vec<String> = subflake
+        .override_input_args()
+        .into_iter()
+        .chain(nix::flake_outputs::eval_args(&nix_args))
+        .collect();
+    let buildables =
+        nix::flake_outputs::buildables(cmd, &subflake_url, systems, &eval_args).await?;
+    tracing::debug!("Outputs to build: {:?}", buildables);
+    nix::flake_outputs::build(cmd, &buildables, &nix_args).await
 }
 
 pub async fn check_nix_version(flake_url: &FlakeUrl, nix_info: &NixInfo) -> anyhow::Result<()> {
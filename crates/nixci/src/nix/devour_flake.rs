@@ -1,16 +1,19 @@
 //! Rust support for invoking <https://github.com/srid/devour-flake>
 
 use anyhow::{bail, Context, Result};
-use nix_rs::command::NixCmd;
+use nix_rs::{
+    build::BuildResult,
+    command::{capability::Capability, NixCmd},
+    store::DrvOut,
+};
 use std::{collections::HashSet, path::PathBuf, process::Stdio, str::FromStr};
 use tokio::io::{AsyncBufReadExt, BufReader};
 
-use super::nix_store::DrvOut;
-
 /// Absolute path to the devour-flake executable
 ///
-/// We expect this environment to be set in Nix build and shell.
-pub const DEVOUR_FLAKE: &str = env!("DEVOUR_FLAKE");
+/// We expect this environment to be set in Nix build and shell. Without it,
+/// only native evaluation (see [crate::nix::flake_outputs]) is available.
+pub const DEVOUR_FLAKE: Option<&str> = option_env!("DEVOUR_FLAKE");
 
 pub struct DevourFlakeOutput(pub HashSet<DrvOut>);
 
@@ -38,20 +41,20 @@ pub async fn devour_flake(
     verbose: bool,
     args: Vec<String>,
 ) -> Result<DevourFlakeOutput> {
-    // TODO: Use nix_rs here as well
-    // In the context of doing https://github.com/srid/nixci/issues/15
-    let devour_flake_url = format!("{}#default", env!("DEVOUR_FLAKE"));
+    let Some(devour_flake) = DEVOUR_FLAKE else {
+        bail!("devour-flake is unavailable: this build was compiled without DEVOUR_FLAKE set");
+    };
+    let devour_flake_url = format!("{}#default", devour_flake);
+    let print_out_paths = nixcmd.supports(Capability::PrintOutPaths).await;
     let mut cmd = nixcmd.command();
-    cmd.args([
-        "build",
-        &devour_flake_url,
-        "-L",
-        "--no-link",
-        "--print-out-paths",
-        "--override-input",
-        "flake",
-    ])
-    .args(args);
+    cmd.args(["build", &devour_flake_url, "-L", "--no-link"])
+        .arg(if print_out_paths {
+            "--print-out-paths"
+        } else {
+            "--json"
+        })
+        .args(["--override-input", "flake"])
+        .args(args);
     nix_rs::command::trace_cmd(&cmd);
     let mut output_fut = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
     let stderr_handle = output_fut.stderr.take().unwrap();
@@ -76,7 +79,12 @@ pub async fn devour_flake(
         .context("Unable to spawn devour-flake process")?;
     if output.status.success() {
         let stdout = String::from_utf8(output.stdout)?;
-        let v = DevourFlakeOutput::from_str(stdout.trim())?;
+        let out_path = if print_out_paths {
+            stdout.trim().to_string()
+        } else {
+            out_path_from_json(&stdout)?
+        };
+        let v = DevourFlakeOutput::from_str(&out_path)?;
         Ok(v)
     } else {
         let exit_code = output.status.code().unwrap_or(1);
@@ -84,6 +92,16 @@ pub async fn devour_flake(
     }
 }
 
+/// Get the `out` path from the `nix build --json` output of devour-flake
+fn out_path_from_json(stdout: &str) -> Result<String> {
+    let results: Vec<BuildResult> = serde_json::from_str(stdout)?;
+    results
+        .first()
+        .and_then(|result| result.outputs.get("out"))
+        .map(|out| out.0.to_string_lossy().to_string())
+        .context("devour-flake produced no `out` output")
+}
+
 /// Transform `--override-input` arguments to use `flake/` prefix, which
 /// devour_flake expects.
 pub fn transform_override_inputs(args: &mut [String]) {
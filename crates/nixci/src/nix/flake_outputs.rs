@@ -0,0 +1,730 @@
+//! Discover and build the outputs of a flake, without devour-flake
+//!
+//! The outputs built are:
+//! - `packages`, `checks` and `devShells` of each system
+//! - the `program` of each app in `apps`
+//! - `nixosConfigurations.*` and `darwinConfigurations.*` (their `toplevel`)
+//! - `homeConfigurations.*` and `legacyPackages.${system}.homeConfigurations.*`
+//!   (their `activationPackage`)
+//!
+//! Configurations are built only if their system is one of the systems built.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use nix_rs::{
+    build::BuildLog,
+    command::{capability::Capability, NixCmd},
+    flake::{eval::nix_eval_attr_with_args, system::System, url::FlakeUrl},
+    log::{ActivityResult, LogEvent, LogLine, Verbosity},
+    store::{command::NixStoreCmd, path_info::PathInfo, store_dir, DrvOut, StorePath},
+};
+use serde::Deserialize;
+use tokio_stream::StreamExt;
+
+/// Per-system outputs whose attributes are all built
+const PER_SYSTEM_OUTPUTS: [&str; 3] = ["packages", "checks", "devShells"];
+
+/// Configuration outputs, along with the attribute to build in each
+/// configuration
+const CONFIGURATION_OUTPUTS: [(&str, &str); 3] = [
+    ("nixosConfigurations", "config.system.build.toplevel"),
+    ("darwinConfigurations", "config.system.build.toplevel"),
+    ("homeConfigurations", "activationPackage"),
+];
+
+/// Nix bindings used by the `--apply` functions evaluating the outputs
+///
+/// `drv` and `app` turn a derivation and an app into a [Buildable]; `systems`
+/// (an attrset keyed by the systems to build) is bound by [apply].
+const NIX_BINDINGS: &str = r#"
+  drv = d: {
+    drvs = { ${builtins.unsafeDiscardStringContext d.drvPath} = [ d.outputName ]; };
+    path = d.outPath;
+  };
+  # The derivations an app needs are those in the string context of its program
+  app = a: let ctx = builtins.getContext a.program; in {
+    drvs = builtins.mapAttrs (_: c: c.outputs) (builtins.removeAttrs ctx
+      (builtins.filter (p: !(ctx.${p} ? outputs)) (builtins.attrNames ctx)));
+    path = a.program;
+  };
+  forSystems = f: outputs: builtins.mapAttrs f (builtins.intersectAttrs systems outputs);
+  forConfigurations = f: cs: builtins.mapAttrs (_: f) (builtins.removeAttrs cs
+    (builtins.filter (n: !(systems ? ${cs.${n}.pkgs.stdenv.hostPlatform.system}))
+      (builtins.attrNames cs)));
+"#;
+
+/// An output of the flake to build
+#[derive(Debug, Clone, Deserialize)]
+pub struct Buildable {
+    /// The derivations to build, along with their outputs to build
+    pub drvs: BTreeMap<StorePath, Vec<String>>,
+    /// The path this output resolves to (for apps, the program)
+    pub path: PathBuf,
+}
+
+/// The outputs of a flake to build, keyed by attribute (eg:
+/// `packages.x86_64-linux.default`)
+#[derive(Debug, Default)]
+pub struct Buildables(pub BTreeMap<String, Buildable>);
+
+/// Return the outputs of the flake to build for the given systems
+///
+/// `extra_args` are passed to `nix eval` (eg: `--override-input`).
+pub async fn buildables(
+    cmd: &NixCmd,
+    url: &FlakeUrl,
+    systems: &[System],
+    extra_args: &[String],
+) -> Result<Buildables> {
+    let mut buildables = Buildables::default();
+    let per_system = [("apps", "app")]
+        .into_iter()
+        .chain(PER_SYSTEM_OUTPUTS.into_iter().map(|output| (output, "drv")));
+    for (output, to_buildable) in per_system {
+        let body = format!("forSystems (_: builtins.mapAttrs (_: {}))", to_buildable);
+        let by_system: BTreeMap<String, BTreeMap<String, Buildable>> =
+            eval_output(cmd, url, output, &apply(systems, &body), extra_args).await?;
+        for (system, outputs) in by_system {
+            buildables
+                .0
+                .extend(outputs.into_iter().map(|(name, buildable)| {
+                    let attr = format!("{}.{}.{}", output, quote(&system), quote(&name));
+                    (attr, buildable)
+                }));
+        }
+    }
+
+    let mut configurations: Vec<(String, &str)> = CONFIGURATION_OUTPUTS
+        .iter()
+        .map(|(output, build_attr)| (output.to_string(), *build_attr))
+        .collect();
+    configurations.extend(systems.iter().map(|system| {
+        (
+            format!(
+                "legacyPackages.{}.homeConfigurations",
+                quote(system.as_ref())
+            ),
+            "activationPackage",
+        )
+    }));
+    for (output, build_attr) in configurations {
+        let body = format!("forConfigurations (c: drv c.{})", build_attr);
+        let outputs: BTreeMap<String, Buildable> =
+            eval_output(cmd, url, &output, &apply(systems, &body), extra_args).await?;
+        buildables
+            .0
+            .extend(outputs.into_iter().map(|(name, buildable)| {
+                let attr = format!("{}.{}.{}", output, quote(&name), build_attr);
+                (attr, buildable)
+            }));
+    }
+    Ok(buildables)
+}
+
+/// The `--apply` function evaluating `body` with [NIX_BINDINGS] in scope
+fn apply(systems: &[System], body: &str) -> String {
+    format!(
+        "let systems = {}; {} in {}",
+        systems_attrset(systems),
+        NIX_BINDINGS,
+        body
+    )
+}
+
+/// Evaluate `output` of the flake, with `--apply`, returning the default
+/// value if the flake does not have the output
+async fn eval_output<T>(
+    cmd: &NixCmd,
+    url: &FlakeUrl,
+    output: &str,
+    apply: &str,
+    extra_args: &[String],
+) -> Result<T>
+where
+    T: Default + serde::de::DeserializeOwned,
+{
+    let args: Vec<&str> = ["--apply", apply]
+        .into_iter()
+        .chain(extra_args.iter().map(|s| s.as_str()))
+        .collect();
+    let v = nix_eval_attr_with_args(cmd, &url.with_attr(output), &args)
+        .await
+        .with_context(|| format!("Unable to evaluate {} of {}", output, url))?;
+    Ok(v.unwrap_or_default())
+}
+
+/// Options of `nix build` that only affect building, along with the number
+/// of values each takes
+const BUILD_ONLY_OPTIONS: [(&str, usize); 15] = [
+    ("-j", 1),
+    ("--max-jobs", 1),
+    ("--cores", 1),
+    ("-o", 1),
+    ("--out-link", 1),
+    ("-k", 0),
+    ("--keep-going", 0),
+    ("-K", 0),
+    ("--keep-failed", 0),
+    ("--rebuild", 0),
+    ("-L", 0),
+    ("--print-build-logs", 0),
+    ("--no-link", 0),
+    ("--print-out-paths", 0),
+    ("--dry-run", 0),
+];
+
+/// The arguments among `args` (meant for `nix build`) to pass to `nix eval`
+///
+/// All of them are, except the [BUILD_ONLY_OPTIONS], as evaluation can depend
+/// on any other (eg: `--impure`, `--option allow-import-from-derivation`).
+pub fn eval_args(args: &[String]) -> Vec<String> {
+    let mut eval_args = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match BUILD_ONLY_OPTIONS.iter().find(|(opt, _)| opt == arg) {
+            Some((_, n)) => {
+                iter.by_ref().take(*n).for_each(drop);
+            }
+            None => eval_args.push(arg.clone()),
+        }
+    }
+    eval_args
+}
+
+/// The outcome of building an output of the flake
+#[derive(Debug, Clone)]
+pub struct OutputResult {
+    /// Attribute of the output (eg: `checks.x86_64-linux.integration`)
+    pub attr: String,
+    pub status: OutputStatus,
+    /// The paths produced, if the output was built
+    pub outs: Vec<DrvOut>,
+}
+
+/// Whether, and how, an output was built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputStatus {
+    /// Built by this run
+    Built,
+    /// Already in the store, or fetched from a substituter
+    Cached,
+    /// Failed to build
+    Failed {
+        /// The derivation whose build failed (the output's own derivation,
+        /// or one of its dependencies), if known
+        drv: Option<StorePath>,
+        /// The end of the log of `drv`
+        log_tail: Vec<String>,
+    },
+    /// Not built, because `nix build` stopped at the failure of another
+    /// output (see `--keep-going`)
+    Skipped,
+}
+
+impl OutputStatus {
+    /// Whether the output is available in the store
+    pub fn is_success(&self) -> bool {
+        matches!(self, OutputStatus::Built | OutputStatus::Cached)
+    }
+}
+
+impl Display for OutputStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputStatus::Built => write!(f, "built"),
+            OutputStatus::Cached => write!(f, "cached"),
+            OutputStatus::Failed { .. } => write!(f, "failed"),
+            OutputStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Build the given outputs of the flake with a single `nix build`, returning
+/// the result of each
+///
+/// The build logs go to stderr; `extra_args` are passed to `nix build`.
+pub async fn build(
+    cmd: &NixCmd,
+    buildables: &Buildables,
+    extra_args: &[String],
+) -> Result<Vec<OutputResult>> {
+    if buildables.0.is_empty() {
+        tracing::warn!("No outputs to build");
+        return Ok(vec![]);
+    }
+    let mut drvs: BTreeMap<&StorePath, BTreeSet<&str>> = BTreeMap::new();
+    for buildable in buildables.0.values() {
+        for (drv, outputs) in &buildable.drvs {
+            drvs.entry(drv)
+                .or_default()
+                .extend(outputs.iter().map(|s| s.as_str()));
+        }
+    }
+    let caret = cmd.supports(Capability::DrvOutputsCaret).await;
+    let installables: Vec<String> = drvs
+        .iter()
+        .map(|(drv, outputs)| installable(drv, outputs, caret))
+        .collect();
+    let args: Vec<&str> = ["build", "--no-link"]
+        .into_iter()
+        .chain(installables.iter().map(|s| s.as_str()))
+        .chain(extra_args.iter().map(|s| s.as_str()))
+        .collect();
+
+    let (mut child, log) = cmd.run_with_args_streaming_log(&args)?;
+    let mut build_log = BuildLog::default();
+    tokio::pin!(log);
+    while let Some(line) = log.next().await {
+        match line? {
+            LogLine::Event(event) => {
+                build_log.observe(&event);
+                print_event(&build_log, &event);
+            }
+            LogLine::Text(text) => eprintln!("{}", text),
+        }
+    }
+    let succeeded = child.wait().await?.success();
+
+    // On failure, find out which outputs were built nonetheless, and what
+    // the failed dependencies of the others are
+    let mut valid = BTreeSet::new();
+    let mut requisites = BTreeSet::new();
+    if !succeeded {
+        let paths: Vec<StorePath> = buildables
+            .0
+            .values()
+            .map(|b| store_path_of(&b.path))
+            .collect();
+        valid = PathInfo::from_nix(cmd, &paths, false)
+            .await?
+            .into_iter()
+            .filter_map(|(path, info)| info.map(|_| path))
+            .collect();
+        let dependency_failed: BTreeSet<PathBuf> = buildables
+            .0
+            .values()
+            .flat_map(|b| b.drvs.keys())
+            .filter(|drv| build_log.dependency_failed.contains(drv))
+            .map(|drv| drv.as_path().clone())
+            .collect();
+        if !dependency_failed.is_empty() {
+            requisites = NixStoreCmd::default()
+                .query_requisites_batched(&dependency_failed, false)
+                .await?
+                .into_iter()
+                .collect();
+        }
+    }
+
+    Ok(buildables
+        .0
+        .iter()
+        .map(|(attr, buildable)| {
+            let status = output_status(buildable, &build_log, succeeded, &valid, &requisites);
+            let outs = if status.is_success() {
+                vec![DrvOut(buildable.path.clone())]
+            } else {
+                vec![]
+            };
+            OutputResult {
+                attr: attr.clone(),
+                status,
+                outs,
+            }
+        })
+        .collect())
+}
+
+/// Decide the status of an output from what `nix build` did
+///
+/// If the build did not succeed, `valid` are the output paths in the store,
+/// and `requisites` those of the derivations that were not built because
+/// their dependencies failed.
+fn output_status(
+    buildable: &Buildable,
+    build_log: &BuildLog,
+    succeeded: bool,
+    valid: &BTreeSet<StorePath>,
+    requisites: &BTreeSet<StorePath>,
+) -> OutputStatus {
+    let failed = |drv: &StorePath| OutputStatus::Failed {
+        drv: Some(drv.clone()),
+        log_tail: build_log.log_tail(drv),
+    };
+    let drvs: Vec<&StorePath> = buildable.drvs.keys().collect();
+    if let Some(drv) = drvs.iter().find(|drv| build_log.failed.contains(drv)) {
+        failed(drv)
+    } else if succeeded || valid.contains(&store_path_of(&buildable.path)) {
+        if drvs.iter().any(|drv| build_log.started.contains(drv)) {
+            OutputStatus::Built
+        } else {
+            OutputStatus::Cached
+        }
+    } else if drvs
+        .iter()
+        .any(|drv| build_log.dependency_failed.contains(drv))
+    {
+        // The requisites are those of all such outputs together, so the
+        // failed dependency is known only if there is one
+        let mut candidates = build_log.failed.intersection(requisites);
+        match (candidates.next(), candidates.next()) {
+            (Some(drv), None) => failed(drv),
+            _ => OutputStatus::Failed {
+                drv: None,
+                log_tail: vec![],
+            },
+        }
+    } else if build_log.failed.is_empty() {
+        // Nix failed for a reason other than a build failure
+        OutputStatus::Failed {
+            drv: None,
+            log_tail: vec![],
+        }
+    } else {
+        OutputStatus::Skipped
+    }
+}
+
+/// The installable selecting the given outputs of a derivation
+///
+/// Nix versions without [Capability::DrvOutputsCaret] use `!` instead of `^`.
+fn installable(drv: &StorePath, outputs: &BTreeSet<&str>, caret: bool) -> String {
+    let outputs: Vec<&str> = outputs.iter().copied().collect();
+    let sep = if caret { "^" } else { "!" };
+    format!("{}{}{}", drv, sep, outputs.join(","))
+}
+
+/// Print a log event of `nix build`, similar to how `nix build -L` would
+fn print_event(build_log: &BuildLog, event: &LogEvent) {
+    match event {
+        LogEvent::Start { level, text, .. } if *level <= Verbosity::Info && !text.is_empty() => {
+            eprintln!("{}", text);
+        }
+        LogEvent::Result {
+            id,
+            result: ActivityResult::BuildLogLine(line),
+        } => {
+            let name = build_log
+                .activity_drv(*id)
+                .and_then(|drv| drv.name())
+                .map(|name| name.trim_end_matches(".drv"))
+                .unwrap_or_default();
+            eprintln!("{}> {}", name, line);
+        }
+        LogEvent::Msg { msg, .. } => eprintln!("{}", msg),
+        _ => {}
+    }
+}
+
+/// The store path containing the given path (eg: an app's program)
+fn store_path_of(path: &Path) -> StorePath {
+    let store_dir = store_dir();
+    let root = path
+        .strip_prefix(&store_dir)
+        .ok()
+        .and_then(|rest| rest.components().next())
+        .map_or_else(|| path.to_path_buf(), |name| store_dir.join(name));
+    StorePath::new(root)
+}
+
+/// A Nix attrset with the given systems as keys
+fn systems_attrset(systems: &[System]) -> String {
+    let keys: Vec<String> = systems
+        .iter()
+        .map(|s| format!("{} = null;", quote(s.as_ref())))
+        .collect();
+    format!("{{ {} }}", keys.join(" "))
+}
+
+/// Quote an attribute name, if needed, for use in an attribute path
+fn quote(name: &str) -> String {
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-'".contains(c));
+    if is_identifier {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix_rs::command::runner::{Recording, ReplayRunner};
+
+    use super::*;
+
+    const HELLO_DRV: &str = "/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv";
+    const HELLO: &str = "/nix/store/9bwryidal9q3g91cjm6xschfn4ikd82q-hello-2.12.1";
+
+    /// A recording of `nix eval` on `output` of the flake, applying `body`;
+    /// the output is missing if there is no `stdout`
+    fn eval_recording(
+        url: &FlakeUrl,
+        systems: &[System],
+        output: &str,
+        body: &str,
+        stdout: Option<&str>,
+    ) -> Recording {
+        let url = url.with_attr(output).to_string();
+        Recording {
+            args: ["eval", &url, "--json", "--apply", &apply(systems, body)]
+                .map(String::from)
+                .to_vec(),
+            stdout: stdout.unwrap_or_default().to_string(),
+            stderr: match stdout {
+                Some(_) => String::new(),
+                None => format!(
+                    "error: flake 'github:juspay/example' does not provide attribute '{}'\n",
+                    output
+                ),
+            },
+            exit_code: if stdout.is_some() { 0 } else { 1 },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_buildables() {
+        let url: FlakeUrl = "github:juspay/example".parse().unwrap();
+        let systems = [System::from("x86_64-linux")];
+        let per_system =
+            |to_buildable: &str| format!("forSystems (_: builtins.mapAttrs (_: {}))", to_buildable);
+        let recording = |output: &str, body: &str, stdout: Option<&str>| {
+            eval_recording(&url, &systems, output, body, stdout)
+        };
+        let mut cmd = NixCmd::default();
+        cmd.with_runner(ReplayRunner::new(vec![
+            recording(
+                "apps",
+                &per_system("app"),
+                Some(&format!(
+                    r#"{{"x86_64-linux":{{"default":{{"drvs":{{"{}":["out"]}},"path":"{}/bin/hello"}}}}}}"#,
+                    HELLO_DRV, HELLO
+                )),
+            ),
+            recording(
+                "packages",
+                &per_system("drv"),
+                Some(&format!(
+                    r#"{{"x86_64-linux":{{"default":{{"drvs":{{"{}":["out"]}},"path":"{}"}}}}}}"#,
+                    HELLO_DRV, HELLO
+                )),
+            ),
+            recording("checks", &per_system("drv"), Some("{}")),
+            recording("devShells", &per_system("drv"), None),
+            // The only configuration is for `aarch64-linux`, which is not built
+            recording(
+                "nixosConfigurations",
+                "forConfigurations (c: drv c.config.system.build.toplevel)",
+                Some("{}"),
+            ),
+            recording(
+                "darwinConfigurations",
+                "forConfigurations (c: drv c.config.system.build.toplevel)",
+                None,
+            ),
+            recording(
+                "homeConfigurations",
+                "forConfigurations (c: drv c.activationPackage)",
+                None,
+            ),
+            recording(
+                "legacyPackages.x86_64-linux.homeConfigurations",
+                "forConfigurations (c: drv c.activationPackage)",
+                None,
+            ),
+        ]));
+
+        let buildables = buildables(&cmd, &url, &systems, &[]).await.unwrap();
+        assert_eq!(
+            buildables.0.keys().collect::<Vec<_>>(),
+            ["apps.x86_64-linux.default", "packages.x86_64-linux.default"]
+        );
+        let app = &buildables.0["apps.x86_64-linux.default"];
+        assert_eq!(app.path, Path::new(HELLO).join("bin/hello"));
+        assert_eq!(
+            app.drvs,
+            BTreeMap::from([(
+                StorePath::new(PathBuf::from(HELLO_DRV)),
+                vec!["out".to_string()]
+            )])
+        );
+    }
+
+    /// Excerpt of `nix build --log-format internal-json`, where `broken`
+    /// fails, `app` depends on it, and `hello` is built
+    const BUILD_LOG: &str = r#"@nix {"action":"start","fields":["/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv","",1,1],"id":1,"level":3,"parent":0,"text":"building '/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv'","type":105}
+@nix {"action":"start","fields":["/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv","",1,1],"id":2,"level":3,"parent":0,"text":"building '/nix/store/g0qf6k8ljf1jxjk1xvpv4cm4lj7sbyzy-hello-2.12.1.drv'","type":105}
+@nix {"action":"result","fields":["make: *** [Makefile:2: all] Error 1"],"id":1,"type":101}
+@nix {"action":"stop","id":1}
+@nix {"action":"stop","id":2}
+@nix {"action":"msg","level":0,"msg":"error: builder for '/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv' failed with exit code 2"}
+@nix {"action":"msg","level":0,"msg":"error: 1 dependencies of derivation '/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-app.drv' failed to build"}"#;
+
+    fn build_log(log: &str) -> BuildLog {
+        let mut build_log = BuildLog::default();
+        for line in log.lines() {
+            if let LogLine::Event(event) = LogLine::parse(line).unwrap() {
+                build_log.observe(&event);
+            }
+        }
+        build_log
+    }
+
+    fn buildable(drv: &str, path: &str) -> Buildable {
+        Buildable {
+            drvs: BTreeMap::from([(StorePath::new(PathBuf::from(drv)), vec!["out".to_string()])]),
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn test_output_status() {
+        let path = |s: &str| StorePath::new(PathBuf::from(s));
+        let broken_drv = "/nix/store/0rxb3ixzk4zaqivc9s795m0a3679wbw2-broken.drv";
+        let app_drv = "/nix/store/zbqsa1s4bbqd3b6y2rw2kl7i6m5m9zpz-app.drv";
+        let cached = "/nix/store/7kl3ghbqh8fb6b2wgx2nrbh7bqxqyl14-openssl-3.0.13";
+        let hello = buildable(HELLO_DRV, HELLO);
+        let openssl = buildable(
+            "/nix/store/3ph5bq8jbyd1pn0i7aya0yyrzqfkh4rr-openssl-3.0.13.drv",
+            cached,
+        );
+        let broken = buildable(
+            broken_drv,
+            "/nix/store/ab5wz9rwbisbqsvsl7xckv1ln0hwm4xq-broken",
+        );
+        let app = buildable(
+            app_drv,
+            "/nix/store/xd6kq3ld0xdh1xrm0rnwvqwinx59a1ab-app/bin/app",
+        );
+        let other = buildable(
+            "/nix/store/hsj8mwn9vzlyaxzmwyf111scisnjhlkb-other.drv",
+            "/nix/store/hsj8mwn9vzlyaxzmwyf111scisnjhlkb-other",
+        );
+
+        let log = build_log(BUILD_LOG);
+        let valid = BTreeSet::from([path(HELLO), path(cached)]);
+        let requisites = BTreeSet::from([path(app_drv), path(broken_drv)]);
+        let status = |b: &Buildable| output_status(b, &log, false, &valid, &requisites);
+        assert_eq!(status(&hello), OutputStatus::Built);
+        assert_eq!(status(&openssl), OutputStatus::Cached);
+        let broken_status = OutputStatus::Failed {
+            drv: Some(path(broken_drv)),
+            log_tail: vec!["make: *** [Makefile:2: all] Error 1".to_string()],
+        };
+        assert_eq!(status(&broken), broken_status);
+        // Failed because of its dependency
+        assert_eq!(status(&app), broken_status);
+        // Cancelled when `broken` failed
+        assert_eq!(status(&other), OutputStatus::Skipped);
+
+        // The failed dependency is unknown when several builds failed
+        let mut log = build_log(BUILD_LOG);
+        let other_drv = path("/nix/store/hsj8mwn9vzlyaxzmwyf111scisnjhlkb-other.drv");
+        log.failed.insert(other_drv.clone());
+        let requisites = BTreeSet::from([path(app_drv), path(broken_drv), other_drv]);
+        assert_eq!(
+            output_status(&app, &log, false, &valid, &requisites),
+            OutputStatus::Failed {
+                drv: None,
+                log_tail: vec![]
+            }
+        );
+
+        // Nix failed without any build failing (eg: an evaluation error)
+        let empty = BTreeSet::new();
+        assert_eq!(
+            output_status(&other, &BuildLog::default(), false, &empty, &empty),
+            OutputStatus::Failed {
+                drv: None,
+                log_tail: vec![]
+            }
+        );
+        // All outputs are in the store when the build succeeds
+        assert_eq!(
+            output_status(&openssl, &BuildLog::default(), true, &empty, &empty),
+            OutputStatus::Cached
+        );
+    }
+
+    #[test]
+    fn test_installable() {
+        let drv = StorePath::new(PathBuf::from(HELLO_DRV));
+        let outputs = BTreeSet::from(["out", "man"]);
+        assert_eq!(
+            installable(&drv, &outputs, true),
+            format!("{}^man,out", HELLO_DRV)
+        );
+        assert_eq!(
+            installable(&drv, &outputs, false),
+            format!("{}!man,out", HELLO_DRV)
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("default"), "default");
+        assert_eq!(quote("x86_64-linux"), "x86_64-linux");
+        assert_eq!(quote("foo.bar"), "\"foo.bar\"");
+        assert_eq!(quote("1password"), "\"1password\"");
+        assert_eq!(
+            systems_attrset(&[System::from("aarch64-darwin")]),
+            "{ aarch64-darwin = null; }"
+        );
+    }
+
+    #[test]
+    fn test_eval_args() {
+        let args: Vec<String> = [
+            "--refresh",
+            "-j",
+            "auto",
+            "--override-input",
+            "foo",
+            "github:owner/foo",
+            "--rebuild",
+            "--impure",
+            "-L",
+            "--option",
+            "allow-import-from-derivation",
+            "true",
+            "--max-jobs",
+            "4",
+        ]
+        .map(String::from)
+        .to_vec();
+        assert_eq!(
+            eval_args(&args),
+            [
+                "--refresh",
+                "--override-input",
+                "foo",
+                "github:owner/foo",
+                "--impure",
+                "--option",
+                "allow-import-from-derivation",
+                "true"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_store_path_of() {
+        assert_eq!(
+            store_path_of(Path::new(
+                "/nix/store/hsj8mwn9vzlyaxzmwyf111scisnjhlkb-bar-0.1.0.0/bin/bar"
+            )),
+            StorePath::new(PathBuf::from(
+                "/nix/store/hsj8mwn9vzlyaxzmwyf111scisnjhlkb-bar-0.1.0.0"
+            ))
+        );
+    }
+}
@@ -6,7 +6,7 @@ use nix_rs::{command::NixCmd, flake::url::FlakeUrl};
 /// Make sure that the `flake.lock` file is in sync.
 pub async fn nix_flake_lock_check(nixcmd: &NixCmd, url: &FlakeUrl) -> Result<()> {
     let mut cmd = nixcmd.command();
-    cmd.args(["flake", "lock", "--no-update-lock-file", &url.0]);
+    cmd.args(["flake", "lock", "--no-update-lock-file", &url.to_string()]);
     nix_rs::command::trace_cmd(&cmd);
     let status = cmd.stdin(Stdio::null()).spawn()?.wait().await?;
     if status.success() {
@@ -1,4 +1,4 @@
 pub mod devour_flake;
+pub mod flake_outputs;
 pub mod lock;
-pub mod nix_store;
 pub mod system_list;
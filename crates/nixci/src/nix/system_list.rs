@@ -3,7 +3,10 @@ use std::str::FromStr;
 use anyhow::Result;
 use nix_rs::{
     command::{NixCmd, NixCmdError},
-    flake::{system::System, url::FlakeUrl},
+    flake::{
+        system::System,
+        url::{FlakeUrl, FlakeUrlError},
+    },
 };
 
 /// A flake URL that references a list of systems ([SystemsList])
@@ -25,7 +28,8 @@ impl FromStr for SystemsListFlakeRef {
         } else {
             s.to_string()
         };
-        Ok(SystemsListFlakeRef(FlakeUrl(url)))
+        let url = url.parse().map_err(|e: FlakeUrlError| e.to_string())?;
+        Ok(SystemsListFlakeRef(url))
     }
 }
 
@@ -48,7 +52,7 @@ impl SystemsList {
     /// Handle known repos of <https://github.com/nix-systems> thereby avoiding
     /// network calls.
     fn from_known_flake(url: &SystemsListFlakeRef) -> Option<Self> {
-        match url.0 .0.as_str() {
+        match url.0.to_string().as_str() {
             "github:nix-systems/empty" => Some(SystemsList(vec![])),
             "github:nix-systems/default-darwin" => Some(SystemsList(vec![
                 "aarch64-darwin".into(),
@@ -72,7 +76,7 @@ where
     T: Default + serde::de::DeserializeOwned,
 {
     let flake_path =
-        nix_eval_impure_expr::<String>(cmd, format!("builtins.getFlake \"{}\"", url.0)).await?;
+        nix_eval_impure_expr::<String>(cmd, format!("builtins.getFlake \"{}\"", url)).await?;
     let v = nix_eval_impure_expr(cmd, format!("import {}", flake_path)).await?;
     Ok(v)
 }
@@ -95,7 +99,7 @@ mod tests {
     async fn test_empty_systems_list() {
         let systems = SystemsList::from_flake(
             &NixCmd::default(),
-            &SystemsListFlakeRef(FlakeUrl("github:nix-systems/empty".to_string())),
+            &SystemsListFlakeRef("github:nix-systems/empty".parse().unwrap()),
         )
         .await
         .unwrap();
@@ -0,0 +1,176 @@
+//! Summary of what `nixci build` built, and what failed
+use colored::Colorize;
+use nix_rs::store::DrvOut;
+use tabled::{settings::Style, Table, Tabled};
+
+use crate::nix::flake_outputs::{OutputResult, OutputStatus};
+
+/// The results of building a subflake
+pub struct SubflakeResult {
+    /// Name of the subflake (`<config>.<subflake>`)
+    pub name: String,
+    /// The result of each output, or the error that prevented building them
+    pub result: anyhow::Result<Vec<OutputResult>>,
+}
+
+impl SubflakeResult {
+    /// Whether the subflake, or any of its outputs, failed
+    pub fn failed(&self) -> bool {
+        match &self.result {
+            Ok(outputs) => outputs.iter().any(|o| !o.status.is_success()),
+            Err(_) => true,
+        }
+    }
+}
+
+/// The results of all subflakes built
+#[derive(Default)]
+pub struct Summary(pub Vec<SubflakeResult>);
+
+#[derive(Tabled)]
+struct Row {
+    subflake: String,
+    output: String,
+    status: String,
+    /// The failed derivation, or error
+    details: String,
+}
+
+impl Summary {
+    /// Paths of all the outputs built
+    pub fn outs(&self) -> impl Iterator<Item = &DrvOut> {
+        self.0
+            .iter()
+            .filter_map(|s| s.result.as_ref().ok())
+            .flatten()
+            .flat_map(|o| o.outs.iter())
+    }
+
+    /// Number of outputs (or subflakes, if they could not be built at all)
+    /// that failed
+    pub fn failures(&self) -> usize {
+        self.count(|status| matches!(status, OutputStatus::Failed { .. }), 1)
+    }
+
+    /// Number of outputs that were not built, because of the failure of
+    /// others
+    pub fn skipped(&self) -> usize {
+        self.count(|status| *status == OutputStatus::Skipped, 0)
+    }
+
+    /// Number of outputs with a matching status, counting each subflake that
+    /// could not be built at all as `per_error`
+    fn count(&self, matches: impl Fn(&OutputStatus) -> bool, per_error: usize) -> usize {
+        self.0
+            .iter()
+            .map(|s| match &s.result {
+                Ok(outputs) => outputs.iter().filter(|o| matches(&o.status)).count(),
+                Err(_) => per_error,
+            })
+            .sum()
+    }
+
+    /// Print the summary table to stderr, followed by the details of each
+    /// failure
+    pub fn print(&self) {
+        let mut rows = vec![];
+        for subflake in &self.0 {
+            match &subflake.result {
+                Ok(outputs) => rows.extend(outputs.iter().map(|o| Row {
+                    subflake: subflake.name.clone(),
+                    output: o.attr.clone(),
+                    status: o.status.to_string(),
+                    details: match &o.status {
+                        OutputStatus::Failed { drv: Some(drv), .. } => drv.to_string(),
+                        _ => String::new(),
+                    },
+                })),
+                Err(err) => rows.push(Row {
+                    subflake: subflake.name.clone(),
+                    output: "-".to_string(),
+                    status: "error".to_string(),
+                    details: err.to_string(),
+                }),
+            }
+        }
+        if rows.is_empty() {
+            return;
+        }
+        let mut table = Table::new(rows);
+        table.with(Style::rounded());
+        eprintln!("{}", "📋 Summary".bold());
+        eprintln!("{}", table);
+
+        for subflake in &self.0 {
+            match &subflake.result {
+                Ok(outputs) => {
+                    for o in outputs {
+                        if let OutputStatus::Failed {
+                            drv: Some(drv),
+                            log_tail,
+                        } = &o.status
+                        {
+                            if log_tail.is_empty() {
+                                continue;
+                            }
+                            eprintln!(
+                                "{}",
+                                format!(
+                                    "❌ {} {}: last {} log lines of {}",
+                                    subflake.name,
+                                    o.attr,
+                                    log_tail.len(),
+                                    drv
+                                )
+                                .red()
+                            );
+                            for line in log_tail {
+                                eprintln!("    {}", line);
+                            }
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{}", format!("❌ {}: {:#}", subflake.name, err).red());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(status: OutputStatus) -> OutputResult {
+        OutputResult {
+            attr: "checks.x86_64-linux.test".to_string(),
+            status,
+            outs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_failures_exclude_skipped() {
+        let summary = Summary(vec![
+            SubflakeResult {
+                name: "default.root".to_string(),
+                result: Ok(vec![
+                    output(OutputStatus::Built),
+                    output(OutputStatus::Failed {
+                        drv: None,
+                        log_tail: vec![],
+                    }),
+                    output(OutputStatus::Skipped),
+                    output(OutputStatus::Skipped),
+                ]),
+            },
+            SubflakeResult {
+                name: "default.doc".to_string(),
+                result: Err(anyhow::anyhow!("evaluation failed")),
+            },
+        ]);
+        assert_eq!(summary.failures(), 2);
+        assert_eq!(summary.skipped(), 2);
+    }
+}
@@ -0,0 +1,286 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use nix_rs::{
+    command::NixCmd,
+    flake::{
+        lock::{FlakeLock, NodeInput},
+        metadata::FlakeMetadata,
+        url::FlakeUrl,
+    },
+};
+use serde::Serialize;
+
+use super::show::{FlakeOutputTable, Row};
+
+/// Inspect and audit flakes
+#[derive(Parser, Debug)]
+pub struct FlakeConfig {
+    #[clap(subcommand)]
+    command: FlakeCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum FlakeCommand {
+    Inputs(InputsConfig),
+}
+
+impl FlakeConfig {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        match &self.command {
+            FlakeCommand::Inputs(config) => config.run().await,
+        }
+    }
+}
+
+/// Audit the inputs of a flake for duplicates, missing `follows` and staleness
+#[derive(Parser, Debug)]
+pub struct InputsConfig {
+    /// The flake whose inputs to audit
+    #[arg(name = "FLAKE", default_value = ".")]
+    pub flake_url: FlakeUrl,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl InputsConfig {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let lock = self.get_lock().await?;
+        let report = InputsReport::new(&lock, SystemTime::now());
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            report.print();
+        }
+        Ok(())
+    }
+
+    /// Read the lock file directly for local flakes, falling back to `nix flake metadata`
+    async fn get_lock(&self) -> anyhow::Result<FlakeLock> {
+        if let Some(path) = self.flake_url.as_local_path() {
+            if path.join("flake.lock").exists() {
+                return FlakeLock::from_flake_dir(path)
+                    .with_context(|| format!("Unable to read lock file of {}", path.display()));
+            }
+        }
+        let metadata = FlakeMetadata::from_nix(NixCmd::get().await, &self.flake_url)
+            .await
+            .with_context(|| "Unable to fetch flake metadata")?;
+        Ok(metadata.locks)
+    }
+}
+
+/// Result of auditing the inputs of a flake
+#[derive(Serialize, Debug)]
+pub struct InputsReport {
+    /// All inputs, including transitive ones
+    pub inputs: Vec<Input>,
+    /// Sources pinned at more than one revision
+    pub duplicates: Vec<DuplicateInput>,
+    /// Transitive inputs that could follow an input of the flake itself
+    pub missing_follows: Vec<MissingFollows>,
+}
+
+/// An input (direct or transitive) of a flake
+#[derive(Serialize, Debug)]
+pub struct Input {
+    /// Path of input names from the flake itself (eg: `flake-parts/nixpkgs-lib`)
+    pub path: String,
+    /// The input path this input follows, if any
+    pub follows: Option<String>,
+    /// The locked URL of the input
+    pub url: Option<FlakeUrl>,
+    /// Time of the last commit, in seconds since the Unix epoch
+    pub last_modified: Option<u64>,
+    /// Number of days since [Input::last_modified]
+    pub age_days: Option<u64>,
+}
+
+/// A source pinned at more than one revision
+#[derive(Serialize, Debug)]
+pub struct DuplicateInput {
+    /// The source, without any revision
+    pub source: FlakeUrl,
+    /// Number of distinct revisions pinned
+    pub revisions: usize,
+    /// Paths of the inputs pinning this source
+    pub paths: Vec<String>,
+}
+
+/// A transitive input that could follow an input of the flake itself
+#[derive(Serialize, Debug)]
+pub struct MissingFollows {
+    /// Path of the transitive input
+    pub path: String,
+    /// The direct input it could follow
+    pub follows: String,
+    /// The `flake.nix` line that makes it follow
+    pub suggestion: String,
+}
+
+impl InputsReport {
+    pub fn new(lock: &FlakeLock, now: SystemTime) -> Self {
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let mut walked = vec![];
+        walk_inputs(lock, &lock.root, &mut vec![], &mut walked);
+
+        let inputs = walked
+            .iter()
+            .map(|(path, input)| {
+                let locked = lock
+                    .resolve_input(input)
+                    .and_then(|n| lock.node(n))
+                    .and_then(|n| n.locked.as_ref());
+                let last_modified = locked.and_then(|l| l.last_modified);
+                Input {
+                    path: path.join("/"),
+                    follows: match input {
+                        NodeInput::Follows(p) => Some(p.join("/")),
+                        NodeInput::Node(_) => None,
+                    },
+                    url: locked.map(|l| l.to_flake_url()),
+                    last_modified,
+                    age_days: last_modified.map(|t| now.saturating_sub(t) / (24 * 60 * 60)),
+                }
+            })
+            .collect();
+
+        // Paths of the inputs that own (rather than follow) the given node
+        let paths_of = |node: &str| -> Vec<String> {
+            walked
+                .iter()
+                .filter(|(_, input)| matches!(input, NodeInput::Node(n) if n == node))
+                .map(|(path, _)| path.join("/"))
+                .collect()
+        };
+
+        let duplicates = lock
+            .duplicates()
+            .into_iter()
+            .filter_map(|nodes| {
+                let locked = lock.node(nodes[0])?.locked.as_ref()?;
+                Some(DuplicateInput {
+                    source: locked.source_url(),
+                    revisions: lock.revisions(&nodes),
+                    paths: nodes.iter().flat_map(|n| paths_of(n)).collect(),
+                })
+            })
+            .collect();
+
+        let direct = lock
+            .resolved_inputs(&lock.root)
+            .into_iter()
+            .filter_map(|(name, node)| Some((name, node, lock.node(node)?.locked.as_ref()?)))
+            .collect::<Vec<_>>();
+        let missing_follows = walked
+            .iter()
+            .filter(|(path, _)| path.len() > 1)
+            .filter_map(|(path, input)| {
+                let NodeInput::Node(node) = input else {
+                    return None;
+                };
+                let locked = lock.node(node)?.locked.as_ref()?;
+                let (name, _, _) = direct
+                    .iter()
+                    .find(|(_, n, l)| n != node && l.same_source(locked))?;
+                Some(MissingFollows {
+                    path: path.join("/"),
+                    follows: name.to_string(),
+                    suggestion: format!("inputs.{}.follows = \"{}\";", path.join(".inputs."), name),
+                })
+            })
+            .collect();
+
+        InputsReport {
+            inputs,
+            duplicates,
+            missing_follows,
+        }
+    }
+
+    /// Print the report as tables to stdout
+    pub fn print(&self) {
+        FlakeOutputTable {
+            rows: self
+                .inputs
+                .iter()
+                .map(|input| Row {
+                    name: input.path.clone(),
+                    description: match (&input.follows, &input.url, input.age_days) {
+                        (Some(follows), _, _) => format!("follows {}", follows),
+                        (None, Some(url), Some(age)) => format!("{} ({} days old)", url, age),
+                        (None, Some(url), None) => url.to_string(),
+                        (None, None, _) => "N/A".to_string(),
+                    },
+                })
+                .collect(),
+            title: "📥 Inputs".to_string(),
+            command: None,
+        }
+        .print();
+
+        FlakeOutputTable {
+            rows: self
+                .duplicates
+                .iter()
+                .map(|dup| Row {
+                    name: dup.source.to_string(),
+                    description: format!("{} revisions: {}", dup.revisions, dup.paths.join(", ")),
+                })
+                .collect(),
+            title: "👯 Duplicate inputs".to_string(),
+            command: None,
+        }
+        .print();
+
+        FlakeOutputTable {
+            rows: self
+                .missing_follows
+                .iter()
+                .map(|m| Row {
+                    name: m.path.clone(),
+                    description: m.suggestion.clone(),
+                })
+                .collect(),
+            title: "🔗 Missing follows".to_string(),
+            command: Some("add to flake.nix".to_string()),
+        }
+        .print();
+
+        if self.duplicates.is_empty() && self.missing_follows.is_empty() {
+            println!("{}", "✅ No duplicate inputs".green().bold());
+        }
+    }
+}
+
+/// Collect all input paths reachable from the given node, without descending
+/// into inputs that follow others.
+fn walk_inputs<'a>(
+    lock: &'a FlakeLock,
+    node: &str,
+    prefix: &mut Vec<String>,
+    acc: &mut Vec<(Vec<String>, &'a NodeInput)>,
+) {
+    let Some(node) = lock.node(node) else {
+        return;
+    };
+    // Guard against malformed lock files with cycles
+    if prefix.len() > lock.nodes.len() {
+        return;
+    }
+    for (name, input) in node.inputs.iter() {
+        prefix.push(name.clone());
+        acc.push((prefix.clone(), input));
+        if let NodeInput::Node(child) = input {
+            walk_inputs(lock, child, prefix, acc);
+        }
+        prefix.pop();
+    }
+}
@@ -3,9 +3,11 @@ use clap_verbosity_flag::{InfoLevel, Verbosity};
 
 pub mod ci;
 mod completion;
+pub mod flake;
 pub mod health;
 pub mod init;
 pub mod show;
+pub mod store;
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
@@ -13,10 +15,14 @@ pub enum Command {
 
     Init(init::InitConfig),
 
-    CI(ci::CIConfig),
+    CI(Box<ci::CIConfig>),
 
     Health(health::HealthConfig),
 
+    Flake(flake::FlakeConfig),
+
+    Store(store::StoreConfig),
+
     /// Generates shell completion scripts
     Completion {
         #[arg(value_enum)]
@@ -31,6 +37,8 @@ impl Command {
             Command::Init(config) => config.run().await,
             Command::CI(config) => config.run(verbosity).await,
             Command::Health(config) => config.run().await,
+            Command::Flake(config) => config.run().await,
+            Command::Store(config) => config.run().await,
             Command::Completion { shell } => completion::generate_completion(*shell),
         }
     }
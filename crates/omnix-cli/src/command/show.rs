@@ -6,9 +6,17 @@ use colored::Colorize;
 use nix_rs::{
     command::NixCmd,
     config::NixConfig,
-    flake::{outputs::Leaf, url::FlakeUrl, Flake},
+    flake::{
+        lock::FlakeLock,
+        lock::NodeInput,
+        outputs::Leaf,
+        schema::{FlakeSchema, MultiSystemSchema},
+        url::FlakeUrl,
+        Flake,
+    },
 };
 use tabled::{
+    builder::Builder,
     settings::{location::ByColumnName, Color, Modify, Style},
     Table, Tabled,
 };
@@ -19,6 +27,10 @@ pub struct ShowConfig {
     /// The flake to show outputs for
     #[arg(name = "FLAKE")]
     pub flake_url: FlakeUrl,
+
+    /// Also show which per-system outputs exist on which system
+    #[arg(long = "all-systems")]
+    pub all_systems: bool,
 }
 
 /// Tabular representation of a set of flake outputs (eg: `packages.*`)
@@ -84,6 +96,23 @@ impl Row {
             })
             .collect()
     }
+
+    /// Create a vector of [Row]s from the inputs of the flake itself
+    pub fn vec_from_flake_inputs(locks: &FlakeLock) -> Vec<Row> {
+        locks
+            .root_inputs()
+            .map(|(name, input)| Row {
+                name: name.clone(),
+                description: match input {
+                    NodeInput::Follows(path) => format!("follows {}", path.join("/")),
+                    NodeInput::Node(_) => locks
+                        .input_url(input)
+                        .map(|url| url.to_string())
+                        .unwrap_or_else(|| "N/A".to_owned()),
+                },
+            })
+            .collect()
+    }
 }
 
 impl ShowConfig {
@@ -94,6 +123,25 @@ impl ShowConfig {
             .await
             .with_context(|| "Unable to fetch flake")?;
 
+        if let Some(description) = &flake.metadata.description {
+            println!("{}", description.italic());
+        }
+        if let Some(revision) = flake.metadata.any_revision() {
+            println!("{} {}", "📌 Revision:".blue().bold(), revision);
+        }
+        println!();
+
+        FlakeOutputTable {
+            rows: Row::vec_from_flake_inputs(&flake.metadata.locks),
+            title: "📥 Inputs".to_string(),
+            command: None,
+        }
+        .print();
+
+        if self.all_systems {
+            print_system_matrix(&FlakeSchema::all_systems(&flake.output));
+        }
+
         FlakeOutputTable {
             rows: Row::vec_from_btreemap(flake.schema.packages),
             title: "📦 Packages".to_string(),
@@ -142,6 +190,16 @@ impl ShowConfig {
         }
         .print();
 
+        FlakeOutputTable {
+            rows: Row::vec_from_btreemap(flake.schema.home_configurations),
+            title: "🏠 Home Configurations".to_string(),
+            command: Some(format!(
+                "home-manager switch --flake {}#<name>",
+                self.flake_url
+            )),
+        }
+        .print();
+
         FlakeOutputTable {
             rows: Row::vec_from_btreemap(flake.schema.nixos_modules),
             title: "🔧 NixOS Modules".to_string(),
@@ -150,6 +208,27 @@ impl ShowConfig {
         }
         .print();
 
+        FlakeOutputTable {
+            rows: Row::vec_from_btreemap(flake.schema.darwin_modules),
+            title: "🍏 Darwin Modules".to_string(),
+            command: None,
+        }
+        .print();
+
+        FlakeOutputTable {
+            rows: Row::vec_from_btreemap(flake.schema.home_modules),
+            title: "🏡 Home Modules".to_string(),
+            command: None,
+        }
+        .print();
+
+        FlakeOutputTable {
+            rows: Row::vec_from_btreemap(flake.schema.flake_modules),
+            title: "🧩 Flake Modules".to_string(),
+            command: None,
+        }
+        .print();
+
         FlakeOutputTable {
             rows: Row::vec_from_btreemap(flake.schema.docker_images),
             title: "🐳 Docker Images".to_string(),
@@ -182,3 +261,31 @@ impl ShowConfig {
         Ok(())
     }
 }
+
+/// Print a table of the per-system outputs, with a column for each system
+/// marking whether the output exists on it.
+fn print_system_matrix(schema: &MultiSystemSchema) {
+    let matrix = schema.matrix();
+    if matrix.is_empty() {
+        return;
+    }
+    let systems = schema.systems.keys().collect::<Vec<_>>();
+    let mut builder = Builder::default();
+    builder.push_record(
+        std::iter::once("output".to_string()).chain(systems.iter().map(|s| s.to_string())),
+    );
+    for row in matrix {
+        builder.push_record(std::iter::once(row.attr()).chain(systems.iter().map(|s| {
+            if row.systems.contains(*s) {
+                "✅".to_string()
+            } else {
+                "❌".to_string()
+            }
+        })));
+    }
+    let mut table = builder.build();
+    table.with(Style::rounded());
+    println!("{}", "🖥️  Systems".blue().bold());
+    println!("{}", table);
+    println!();
+}
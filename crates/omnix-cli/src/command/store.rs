@@ -0,0 +1,140 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use nix_rs::{
+    build::BuildResult,
+    command::NixCmd,
+    store::{
+        closure::{Closure, ClosureDiff, PackageChange},
+        StorePath,
+    },
+};
+
+use super::show::{FlakeOutputTable, Row};
+
+/// Inspect the Nix store
+#[derive(Parser, Debug)]
+pub struct StoreConfig {
+    #[clap(subcommand)]
+    command: StoreCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum StoreCommand {
+    Diff(DiffConfig),
+}
+
+impl StoreConfig {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        match &self.command {
+            StoreCommand::Diff(config) => config.run().await,
+        }
+    }
+}
+
+/// Compare the closures of two store paths or installables (eg:
+/// `github:srid/nixos-config/<rev>#nixosConfigurations.foo.config.system.build.toplevel`)
+#[derive(Parser, Debug)]
+pub struct DiffConfig {
+    /// The old store path or installable (built if needed)
+    #[arg(name = "OLD")]
+    pub old: String,
+
+    /// The new store path or installable (built if needed)
+    #[arg(name = "NEW")]
+    pub new: String,
+
+    /// Print the difference as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl DiffConfig {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let cmd = NixCmd::get().await;
+        let old = closure_of(cmd, &self.old).await?;
+        let new = closure_of(cmd, &self.new).await?;
+        let diff = ClosureDiff::new(&old, &new);
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else {
+            print_diff(&diff);
+        }
+        Ok(())
+    }
+}
+
+/// The closure of a store path, or of the outputs of an installable
+async fn closure_of(cmd: &NixCmd, arg: &str) -> anyhow::Result<Closure> {
+    let paths = match arg.parse::<StorePath>() {
+        Ok(path) => vec![path],
+        Err(_) => BuildResult::from_nix(cmd, &[arg], &[])
+            .await
+            .with_context(|| format!("Unable to build {}", arg))?
+            .iter()
+            .flat_map(|result| result.out_paths())
+            .map(|out| out.clone().as_store_path())
+            .collect(),
+    };
+    Closure::from_nix(cmd, &paths)
+        .await
+        .with_context(|| format!("Unable to get the closure of {}", arg))
+}
+
+/// Format a change in size, like `+1.2 MiB`
+pub fn format_size_delta(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, bytesize::ByteSize::b(delta.unsigned_abs()))
+}
+
+fn print_diff(diff: &ClosureDiff) {
+    let rows = |changes: &[PackageChange]| -> Vec<Row> {
+        changes
+            .iter()
+            .map(|c| {
+                let versions = |v: &std::collections::BTreeSet<String>| {
+                    v.iter()
+                        .map(|s| if s.is_empty() { "ε" } else { s.as_str() })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                let versions = match (c.old_versions.is_empty(), c.new_versions.is_empty()) {
+                    (true, _) => versions(&c.new_versions),
+                    (_, true) => versions(&c.old_versions),
+                    _ if c.old_versions == c.new_versions => versions(&c.new_versions),
+                    _ => format!(
+                        "{} → {}",
+                        versions(&c.old_versions),
+                        versions(&c.new_versions)
+                    ),
+                };
+                Row {
+                    name: c.name.clone(),
+                    description: format!("{} ({})", versions, format_size_delta(c.size_delta)),
+                }
+            })
+            .collect()
+    };
+    for (title, changes) in [
+        ("➕ Added", &diff.added),
+        ("➖ Removed", &diff.removed),
+        ("🔀 Changed", &diff.changed),
+    ] {
+        FlakeOutputTable {
+            rows: rows(changes),
+            title: title.to_string(),
+            command: None,
+        }
+        .print();
+    }
+    let summary = format!(
+        "Closure size: {} → {} ({})",
+        bytesize::ByteSize::b(diff.old_size),
+        bytesize::ByteSize::b(diff.new_size),
+        format_size_delta(diff.size_delta())
+    );
+    if diff.is_empty() {
+        println!("{}", "✅ The closures are identical".green().bold());
+    }
+    println!("{}", summary.bold());
+}
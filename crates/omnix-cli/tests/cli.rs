@@ -46,6 +46,42 @@ fn om_show_remote() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// `om flake inputs` reports duplicated inputs of a local flake, without Nix.
+#[test]
+fn om_flake_inputs() -> anyhow::Result<()> {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    std::fs::write(
+        temp_dir.path().join("flake.lock"),
+        r#"{
+          "nodes": {
+            "nixpkgs": { "locked": { "type": "github", "owner": "nixos", "repo": "nixpkgs", "rev": "a", "narHash": "sha256-a", "lastModified": 0 } },
+            "nixpkgs_2": { "locked": { "type": "github", "owner": "nixos", "repo": "nixpkgs", "rev": "b", "narHash": "sha256-b", "lastModified": 0 } },
+            "tool": {
+              "inputs": { "nixpkgs": "nixpkgs_2" },
+              "locked": { "type": "github", "owner": "example", "repo": "tool", "rev": "t", "narHash": "sha256-t" }
+            },
+            "root": { "inputs": { "nixpkgs": "nixpkgs", "tool": "tool" } }
+          },
+          "root": "root",
+          "version": 7
+        }"#,
+    )?;
+    let output = om()?
+        .args(["flake", "inputs", "--json"])
+        .arg(temp_dir.path())
+        .output()?;
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(report["inputs"].as_array().unwrap().len(), 3);
+    assert_eq!(report["duplicates"][0]["source"], "github:nixos/nixpkgs");
+    assert_eq!(
+        report["missing_follows"][0]["suggestion"],
+        "inputs.tool.inputs.nixpkgs.follows = \"nixpkgs\";"
+    );
+    temp_dir.close().unwrap();
+    Ok(())
+}
+
 /// `om init` runs and successfully initializes a template
 #[test]
 fn om_init() -> anyhow::Result<()> {
@@ -91,7 +127,7 @@ mod om_ci_tests {
     use std::path::{Path, PathBuf};
 
     use anyhow::bail;
-    use nixci::{self, nix::nix_store::StorePath};
+    use nix_rs::store::StorePath;
     use regex::Regex;
 
     /// Run `om ci build` passing given arguments, returning its stdout (parsed).
@@ -5,6 +5,8 @@ use std::{collections::BTreeMap, path::PathBuf};
 use dioxus::prelude::*;
 use dioxus_router::components::Link;
 use nix_rs::flake::{
+    lock::NodeInput,
+    metadata::FlakeMetadata,
     outputs::{FlakeOutputs, Leaf, Type, Val},
     schema::FlakeSchema,
     url::FlakeUrl,
@@ -43,8 +45,10 @@ pub fn FlakeInput() -> Element {
                 value: state.get_flake_url_string(),
                 disabled: busy,
                 onchange: move |ev| {
-                    let url: FlakeUrl = str::parse(&ev.value()).unwrap();
-                    Route::go_to_flake(url);
+                    match str::parse::<FlakeUrl>(&ev.value()) {
+                        Ok(url) => Route::go_to_flake(url),
+                        Err(err) => tracing::error!("Invalid flake URL: {}", err),
+                    }
                 }
             }
             div { class: "ml-2 flex flex-col",
@@ -82,11 +86,52 @@ pub fn FlakeView(flake: Flake) -> Element {
             div { class: "text-sm italic text-gray-600",
                 Link { to: Route::FlakeRaw {}, "View raw output" }
             }
+            FlakeMetadataView { metadata: flake.metadata }
             FlakeSchemaView { schema: flake.schema }
         }
     }
 }
 
+#[component]
+pub fn FlakeMetadataView(metadata: FlakeMetadata) -> Element {
+    let inputs: Vec<(String, String)> = metadata
+        .locks
+        .root_inputs()
+        .map(|(name, input)| {
+            let desc = match input {
+                NodeInput::Follows(path) => format!("follows {}", path.join("/")),
+                NodeInput::Node(_) => metadata
+                    .locks
+                    .input_url(input)
+                    .map(|url| url.to_string())
+                    .unwrap_or_default(),
+            };
+            (name.clone(), desc)
+        })
+        .collect();
+    rsx! {
+        div { class: "text-left",
+            match &metadata.description {
+                Some(v) => rsx! { div { class: "my-2 italic", "{v}" } },
+                None => rsx! { "" }
+            },
+            match metadata.any_revision() {
+                Some(v) => rsx! { div { class: "font-mono text-xs text-gray-500", "Revision: ", "{v}" } },
+                None => rsx! { "" }
+            },
+            SectionHeading { title: "Inputs", extra: inputs.len().to_string() }
+            ul {
+                for (name, desc) in inputs {
+                    li { class: "ml-4",
+                        span { class: "px-2 py-1 font-bold text-primary-500", "{name}" }
+                        span { class: "font-mono text-xs text-gray-500", "{desc}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 pub fn SectionHeading(title: &'static str, extra: Option<String>) -> Element {
     rsx! {
@@ -117,7 +162,13 @@ pub fn FlakeSchemaView(schema: FlakeSchema) -> Element {
                 BtreeMapView { title: "Apps", tree: schema.apps }
                 BtreeMapView { title: "NixOS configurations", tree: schema.nixos_configurations }
                 BtreeMapView { title: "Darwin configurations", tree: schema.darwin_configurations }
+                BtreeMapView { title: "Home configurations", tree: schema.home_configurations }
                 BtreeMapView { title: "NixOS modules", tree: schema.nixos_modules }
+                BtreeMapView { title: "Darwin modules", tree: schema.darwin_modules }
+                BtreeMapView { title: "Home modules", tree: schema.home_modules }
+                BtreeMapView { title: "Flake modules", tree: schema.flake_modules }
+                BtreeMapView { title: "Docker images", tree: schema.docker_images }
+                BtreeMapView { title: "Overlays", tree: schema.overlays }
                 SectionHeading { title: "Formatter" }
                 match schema.formatter.as_ref() {
                     Some(l) => {
@@ -204,15 +255,25 @@ pub fn FlakeOutputsRawView(outs: FlakeOutputs) -> Element {
         rsx! {
             span {
                 match type_ {
-                    Type::NixosModule => "nixosModule ❄️",
-                    Type::NixosConfiguration => "nixosConfiguration 🧩",
-                    Type::DarwinConfiguration => "darwinConfiguration 🍏",
-                    Type::Package => "package 📦",
-                    Type::DevShell => "devShell 🐚",
-                    Type::Check => "check 🧪",
-                    Type::App => "app 📱",
-                    Type::Template => "template 🏗️",
-                    Type::Unknown => "unknown ❓",
+                    Type::NixosModule => "nixosModule ❄️".to_string(),
+                    Type::NixosConfiguration => "nixosConfiguration 🧩".to_string(),
+                    Type::DarwinModule => "darwinModule 🍏".to_string(),
+                    Type::DarwinConfiguration => "darwinConfiguration 🍏".to_string(),
+                    Type::HomeModule => "homeModule 🏡".to_string(),
+                    Type::HomeConfiguration => "homeConfiguration 🏠".to_string(),
+                    Type::Package => "package 📦".to_string(),
+                    Type::DevShell => "devShell 🐚".to_string(),
+                    Type::Check => "check 🧪".to_string(),
+                    Type::App => "app 📱".to_string(),
+                    Type::Template => "template 🏗️".to_string(),
+                    Type::Overlay => "overlay 🎨".to_string(),
+                    Type::Formatter => "formatter 🧹".to_string(),
+                    Type::DockerImage => "dockerImage 🐳".to_string(),
+                    Type::Lib => "lib 📚".to_string(),
+                    Type::Schema => "schema 📜".to_string(),
+                    Type::FlakeModule => "flakeModule 🧩".to_string(),
+                    Type::Other(what) => format!("{} 🔹", what),
+                    Type::Unknown => "unknown ❓".to_string(),
                 }
             }
         }
@@ -30,7 +30,7 @@ fn NixInfoView(info: NixInfo) -> Element {
         div { class: "flex flex-col max-w-prose p-4 space-y-8 bg-white border-2 rounded border-base-400",
             div {
                 b { "Nix Version" }
-                div { class: "p-1 my-1 rounded bg-primary-50", NixVersionView { version: info.nix_version } }
+                div { class: "p-1 my-1 rounded bg-primary-50", NixVersionView { version: info.nix_version.clone() } }
             }
             div {
                 b { "Nix Config" }
@@ -86,7 +86,9 @@ fn NixEnvView(env: NixEnv) -> Element {
                 tbody {
                     TableRow { name: "Current User", title: "Logged-in user", code { "{env.current_user}" } }
                     TableRow { name: "OS", title: "Operating System", code { "{env.os}" } }
+                    TableRow { name: "Install", title: "How Nix is installed", code { "{env.install}" } }
                     TableRow { name: "Total disk space", title: "Total disk space on the current machine", code { "{env.total_disk_space}" } }
+                    TableRow { name: "Free disk space", title: "Available disk space on the volume of the Nix store", code { "{env.available_disk_space}" } }
                     TableRow { name: "Total RAM", title: "Total memory on the current machine", code { "{env.total_memory}" } }
                 }
             }
@@ -32,8 +32,13 @@ impl FlakeCache {
     }
 
     /// Look up a [Flake] by [FlakeUrl] in the cache.
+    ///
+    /// Equivalent URLs (see [FlakeUrl::is_equivalent]) share the same entry.
     pub fn get(&self, k: &FlakeUrl) -> Option<Flake> {
-        let (t, flake) = self.0.get(k).and_then(|v| v.as_ref().cloned())?;
+        let (t, flake) = self
+            .0
+            .get(&k.canonicalize())
+            .and_then(|v| v.as_ref().cloned())?;
         tracing::info!("Cache hit for {} (updated: {:?})", k, t);
         Some(flake)
     }
@@ -41,7 +46,8 @@ impl FlakeCache {
     /// Update the cache with a new [Flake].
     pub fn update(&mut self, k: FlakeUrl, flake: Flake) {
         tracing::info!("Caching flake [{}]", &k);
-        self.0.insert(k, Some((SystemTime::now(), flake)));
+        self.0
+            .insert(k.canonicalize(), Some((SystemTime::now(), flake)));
     }
 
     /// Recently updated flakes, along with any unavailable flakes in cache.